@@ -1,5 +1,9 @@
+extern crate self as contextful;
+
 use std::borrow::Cow;
 
+pub use contextful_derive::Context;
+
 pub trait Context: std::fmt::Debug + Clone {}
 
 impl Context for () {}
@@ -19,64 +23,16 @@ mod tests {
     use self::external::*;
     use super::*;
 
-    #[cfg(feature = "todo")]
-    mod todo {
-        // TODO: We want to write this kind of pattern:
-        #[derive(Debug, Clone, Context)]
-        pub struct CommonContext {
-            #[as_context]
-            time: TimeMocker,
-            #[as_context]
-            web: WebMocker,
-            conn: PgConnection,
-        }
-
-        impl AsContext<MiddlewareContext> for CommonContext {
-            fn as_context(&self) -> Cow<'_, MiddlewareContext> {
-                Cow::from(MiddlewareContext {
-                    time: self.time.clone(),
-                    web: self.web.clone(),
-                })
-            }
-        }
-
-        #[derive(Debug, Clone, Context)]
-        pub struct AppContext {
-            #[as_context(TimeMocker, WebMocker)]
-            common: CommonContext,
-            server_config: ServerConfig,
-        }
-
-        #[derive(Debug, Clone, Context)]
-        pub struct MiddlewareContext {
-            #[as_context]
-            time: TimeMocker,
-            #[as_context]
-            web: WebMocker,
-        }
-    }
-
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Context)]
     pub struct CommonContext {
+        #[as_context]
         time: TimeMocker,
+        #[as_context]
         web: WebMocker,
+        #[allow(dead_code)]
         conn: PgConnection,
     }
 
-    impl Context for CommonContext {}
-
-    impl AsContext<TimeMocker> for CommonContext {
-        fn as_context(&self) -> Cow<'_, TimeMocker> {
-            Cow::Borrowed(&self.time)
-        }
-    }
-
-    impl AsContext<WebMocker> for CommonContext {
-        fn as_context(&self) -> Cow<'_, WebMocker> {
-            Cow::Borrowed(&self.web)
-        }
-    }
-
     impl AsContext<MiddlewareContext> for CommonContext {
         fn as_context(&self) -> Cow<'_, MiddlewareContext> {
             Cow::Owned(MiddlewareContext {
@@ -86,64 +42,58 @@ mod tests {
         }
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Context)]
     pub struct AppContext {
+        #[as_context(TimeMocker, WebMocker, MiddlewareContext)]
         common: CommonContext,
+        #[allow(dead_code)]
         server_config: ServerConfig,
     }
 
-    impl Context for AppContext {}
-
-    impl AsContext<CommonContext> for AppContext {
-        fn as_context(&self) -> Cow<'_, CommonContext> {
-            Cow::Borrowed(&self.common)
-        }
-    }
-
-    impl AsContext<TimeMocker> for AppContext {
-        fn as_context(&self) -> Cow<'_, TimeMocker> {
-            self.common.as_context()
-        }
-    }
-
-    impl AsContext<WebMocker> for AppContext {
-        fn as_context(&self) -> Cow<'_, WebMocker> {
-            self.common.as_context()
-        }
-    }
-
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Context)]
     pub struct MiddlewareContext {
+        #[as_context]
         time: TimeMocker,
+        #[as_context]
         web: WebMocker,
     }
 
-    impl Context for MiddlewareContext {}
-
-    impl AsContext<TimeMocker> for MiddlewareContext {
-        fn as_context(&self) -> Cow<'_, TimeMocker> {
-            Cow::Borrowed(&self.time)
-        }
-    }
-
-    impl AsContext<WebMocker> for MiddlewareContext {
-        fn as_context(&self) -> Cow<'_, WebMocker> {
-            Cow::Borrowed(&self.web)
-        }
+    #[test]
+    fn test_as_context() {
+        let ctx = AppContext {
+            common: CommonContext {
+                time: TimeMocker(1),
+                web: WebMocker(2),
+                conn: PgConnection(()),
+            },
+            server_config: ServerConfig(()),
+        };
+
+        let common: Cow<'_, CommonContext> = ctx.as_context();
+        assert!(std::ptr::eq(&*common, &ctx.common));
+        let time: Cow<'_, TimeMocker> = ctx.as_context();
+        assert!(std::ptr::eq(&*time, &ctx.common.time));
+        let web: Cow<'_, WebMocker> = ctx.as_context();
+        assert!(std::ptr::eq(&*web, &ctx.common.web));
+        assert_eq!(web.0, 2);
+        let middleware: Cow<'_, MiddlewareContext> = ctx.as_context();
+        assert!(matches!(middleware, Cow::Owned(_)));
+        let time: Cow<'_, TimeMocker> = middleware.as_context();
+        assert_eq!(time.0, 1);
     }
 
     mod external {
         #[derive(Debug, Clone)]
-        pub struct TimeMocker(());
+        pub struct TimeMocker(pub u32);
 
         #[derive(Debug, Clone)]
-        pub struct WebMocker(());
+        pub struct WebMocker(pub u32);
 
         #[derive(Debug, Clone)]
-        pub struct PgConnection(());
+        pub struct PgConnection(#[allow(dead_code)] pub ());
 
         #[derive(Debug, Clone)]
-        pub struct ServerConfig(());
+        pub struct ServerConfig(#[allow(dead_code)] pub ());
 
         impl super::Context for TimeMocker {}
         impl super::Context for WebMocker {}
@@ -0,0 +1,258 @@
+extern crate proc_macro;
+
+use std::collections::HashMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{DeriveInput, Token};
+
+#[cfg(test)]
+#[macro_use]
+mod test_utils;
+
+#[proc_macro_derive(Context, attributes(as_context))]
+pub fn derive_context(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    derive_context2(input.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn derive_context2(input: TokenStream) -> syn::Result<TokenStream> {
+    let input = syn::parse2::<DeriveInput>(input)?;
+
+    let data = if let syn::Data::Struct(data) = &input.data {
+        data
+    } else {
+        return Err(syn::Error::new(
+            input.span(),
+            "Context cannot be derived for enums or unions",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = &input.generics.split_for_impl();
+
+    // Target type -> the field providing it
+    let mut provided = HashMap::new();
+    let mut impls = Vec::new();
+    for (idx, field) in data.fields.iter().enumerate() {
+        let delegates = if let Some(delegates) = parse_field_attrs(&field.attrs)? {
+            delegates
+        } else {
+            continue;
+        };
+        let member = if let Some(ident) = &field.ident {
+            syn::Member::Named(ident.clone())
+        } else {
+            syn::Member::Unnamed(syn::Index {
+                index: idx as u32,
+                span: field.span(),
+            })
+        };
+        let field_name = member.to_token_stream().to_string();
+
+        let targets =
+            std::iter::once((&field.ty, true)).chain(delegates.iter().map(|ty| (ty, false)));
+        for (target, borrowed) in targets {
+            if is_self_type(target, name) {
+                return Err(syn::Error::new(
+                    target.span(),
+                    "AsContext<Self> is already implemented for every context",
+                ));
+            }
+            let key = target.to_token_stream().to_string();
+            if let Some(other) = provided.insert(key, field_name.clone()) {
+                return Err(syn::Error::new(
+                    target.span(),
+                    format_args!(
+                        "conflicting implementations of AsContext<{}>: also provided through `{}`",
+                        target.to_token_stream(),
+                        other,
+                    ),
+                ));
+            }
+            let body = if borrowed {
+                quote! { std::borrow::Cow::Borrowed(&self.#member) }
+            } else {
+                quote! { contextful::AsContext::<#target>::as_context(&self.#member) }
+            };
+            impls.push(quote! {
+                impl #impl_generics contextful::AsContext<#target> for #name #ty_generics #where_clause {
+                    fn as_context(&self) -> std::borrow::Cow<'_, #target> {
+                        #body
+                    }
+                }
+            });
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics contextful::Context for #name #ty_generics #where_clause {}
+        #(#impls)*
+    })
+}
+
+/// Returns the delegated types if the field has `#[as_context]`.
+fn parse_field_attrs(attrs: &[syn::Attribute]) -> syn::Result<Option<Vec<syn::Type>>> {
+    let mut ret = None;
+    for attr in attrs {
+        if !attr.path.is_ident("as_context") {
+            continue;
+        }
+        if ret.is_some() {
+            return Err(syn::Error::new(
+                attr.span(),
+                "multiple #[as_context] attributes",
+            ));
+        }
+        let delegates = if attr.tokens.is_empty() {
+            Vec::new()
+        } else {
+            attr.parse_args_with(Punctuated::<syn::Type, Token![,]>::parse_terminated)?
+                .into_iter()
+                .collect()
+        };
+        ret = Some(delegates);
+    }
+    Ok(ret)
+}
+
+fn is_self_type(ty: &syn::Type, name: &syn::Ident) -> bool {
+    if let syn::Type::Path(path) = ty {
+        path.qself.is_none()
+            && (path.path.is_ident(name)
+                || path
+                    .path
+                    .is_ident(&syn::Ident::new("Self", Span::call_site())))
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_plain() {
+        assert_ts_eq!(
+            derive_context2(quote! {
+                struct ServerConfig(());
+            })
+            .unwrap(),
+            quote! {
+                impl contextful::Context for ServerConfig {}
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_as_context() {
+        assert_ts_eq!(
+            derive_context2(quote! {
+                struct AppContext {
+                    #[as_context(TimeMocker, WebMocker)]
+                    common: CommonContext,
+                    #[as_context]
+                    server_config: ServerConfig,
+                    conn: PgConnection,
+                }
+            })
+            .unwrap(),
+            quote! {
+                impl contextful::Context for AppContext {}
+                impl contextful::AsContext<CommonContext> for AppContext {
+                    fn as_context(&self) -> std::borrow::Cow<'_, CommonContext> {
+                        std::borrow::Cow::Borrowed(&self.common)
+                    }
+                }
+                impl contextful::AsContext<TimeMocker> for AppContext {
+                    fn as_context(&self) -> std::borrow::Cow<'_, TimeMocker> {
+                        contextful::AsContext::<TimeMocker>::as_context(&self.common)
+                    }
+                }
+                impl contextful::AsContext<WebMocker> for AppContext {
+                    fn as_context(&self) -> std::borrow::Cow<'_, WebMocker> {
+                        contextful::AsContext::<WebMocker>::as_context(&self.common)
+                    }
+                }
+                impl contextful::AsContext<ServerConfig> for AppContext {
+                    fn as_context(&self) -> std::borrow::Cow<'_, ServerConfig> {
+                        std::borrow::Cow::Borrowed(&self.server_config)
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_tuple() {
+        assert_ts_eq!(
+            derive_context2(quote! {
+                struct Ctx<T: Clone>(#[as_context] T);
+            })
+            .unwrap(),
+            quote! {
+                impl<T: Clone> contextful::Context for Ctx<T> {}
+                impl<T: Clone> contextful::AsContext<T> for Ctx<T> {
+                    fn as_context(&self) -> std::borrow::Cow<'_, T> {
+                        std::borrow::Cow::Borrowed(&self.0)
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Context cannot be derived for enums or unions")]
+    fn test_derive_enum() {
+        derive_context2(quote! {
+            enum AppContext {}
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "conflicting implementations of AsContext<TimeMocker>: also provided through `time`"
+    )]
+    fn test_derive_conflict() {
+        derive_context2(quote! {
+            struct AppContext {
+                #[as_context]
+                time: TimeMocker,
+                #[as_context(TimeMocker)]
+                common: CommonContext,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "AsContext<Self> is already implemented for every context")]
+    fn test_derive_self() {
+        derive_context2(quote! {
+            struct AppContext {
+                #[as_context(AppContext)]
+                common: CommonContext,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "multiple #[as_context] attributes")]
+    fn test_derive_multiple_attrs() {
+        derive_context2(quote! {
+            struct AppContext {
+                #[as_context]
+                #[as_context(TimeMocker)]
+                common: CommonContext,
+            }
+        })
+        .unwrap();
+    }
+}
@@ -1,9 +1,15 @@
 use futures::prelude::*;
 
 pub use crate::error::NailsError;
-pub use crate::request::{parse_query, FromBody, FromPath, FromQuery, Preroute};
+pub use crate::query::{parse_nested_query, FromQueryStruct, ToQueryStruct};
+pub use crate::request::{
+    build_uri, match_host, parse_cookies, parse_query, request_host, split_path_component,
+    FromBody, FromCookie, FromHeader, FromPath, FromPathRest, FromQuery, PathConstraint, Preroute,
+    RequestHead, RoutePattern, ToPath, ToPathRest, ToQuery, ToUri,
+};
+pub use crate::session::{FromSession, SessionLoader};
 pub use futures::future::BoxFuture;
-pub use hyper::{Body, Method, Request};
+pub use hyper::{Body, Method, Request, Uri};
 
 pub fn box_future<'a, T: Future + Send + 'a>(x: T) -> BoxFuture<'a, T::Output> {
     x.boxed()
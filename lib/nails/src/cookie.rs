@@ -0,0 +1,281 @@
+use std::fmt;
+use std::time::Duration;
+
+use hyper::header::{HeaderValue, SET_COOKIE};
+use hyper::HeaderMap;
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Browsers only accept `SameSite=None` on secure cookies, so `Secure` is always emitted along with it.
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        })
+    }
+}
+
+/// A cookie to be sent in a `Set-Cookie` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    same_site: Option<SameSite>,
+    secure: bool,
+    http_only: bool,
+}
+
+impl Cookie {
+    /// Creates a cookie without attributes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a token or `value` contains characters not allowed in cookies.
+    pub fn new(name: &str, value: &str) -> Self {
+        assert!(
+            !name.is_empty() && name.bytes().all(is_tchar),
+            "invalid cookie name: {:?}",
+            name
+        );
+        assert!(
+            value.bytes().all(is_cookie_octet),
+            "invalid cookie value: {:?}",
+            value
+        );
+        Self {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            path: None,
+            domain: None,
+            max_age: None,
+            same_site: None,
+            secure: false,
+            http_only: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn path(&mut self, path: &str) -> &mut Self {
+        self.path = Some(attribute_value(path));
+        self
+    }
+
+    pub fn domain(&mut self, domain: &str) -> &mut Self {
+        self.domain = Some(attribute_value(domain));
+        self
+    }
+
+    pub fn max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn same_site(&mut self, same_site: SameSite) -> &mut Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    pub fn secure(&mut self, secure: bool) -> &mut Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn http_only(&mut self, http_only: bool) -> &mut Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn to_header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.to_string()).expect("internal error: invalid Set-Cookie")
+    }
+
+    fn is_same_cookie(&self, other: &Self) -> bool {
+        self.name == other.name && self.path == other.path && self.domain == other.domain
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+        if self.secure || self.same_site == Some(SameSite::None) {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        Ok(())
+    }
+}
+
+/// Cookies to be set on (or removed from) the client along with a response.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a cookie and returns it for setting attributes.
+    ///
+    /// If cookies with the same name, path and domain are added more than once, only the last one is sent.
+    pub fn add(&mut self, name: &str, value: &str) -> &mut Cookie {
+        self.cookies.push(Cookie::new(name, value));
+        self.cookies.last_mut().unwrap()
+    }
+
+    /// Tells the client to discard a cookie.
+    ///
+    /// Its path and domain must match the ones the cookie was set with.
+    pub fn remove(&mut self, name: &str) -> &mut Cookie {
+        self.add(name, "").max_age(Duration::from_secs(0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies
+            .iter()
+            .enumerate()
+            .filter(move |&(i, cookie)| {
+                !self.cookies[i + 1..]
+                    .iter()
+                    .any(|later| later.is_same_cookie(cookie))
+            })
+            .map(|(_, cookie)| cookie)
+    }
+
+    /// Appends a `Set-Cookie` header for each cookie.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        for cookie in self.iter() {
+            headers.append(SET_COOKIE, cookie.to_header_value());
+        }
+    }
+}
+
+// tchar in RFC 7230
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// cookie-octet in RFC 6265
+fn is_cookie_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+fn attribute_value(value: &str) -> String {
+    assert!(
+        value
+            .bytes()
+            .all(|b| (0x20..0x7F).contains(&b) && b != b';'),
+        "invalid cookie attribute: {:?}",
+        value
+    );
+    value.to_owned()
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_attributes() {
+        assert_eq!(Cookie::new("id", "a3fWa").to_string(), "id=a3fWa");
+        assert_eq!(
+            Cookie::new("id", "a3fWa")
+                .path("/")
+                .domain("example.com")
+                .max_age(Duration::from_secs(3600))
+                .same_site(SameSite::Lax)
+                .secure(true)
+                .http_only(true)
+                .to_string(),
+            "id=a3fWa; Path=/; Domain=example.com; Max-Age=3600; SameSite=Lax; Secure; HttpOnly",
+        );
+        assert_eq!(
+            Cookie::new("id", "a3fWa")
+                .same_site(SameSite::None)
+                .to_string(),
+            "id=a3fWa; SameSite=None; Secure",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cookie name")]
+    fn test_cookie_invalid_name() {
+        Cookie::new("session id", "a3fWa");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cookie value")]
+    fn test_cookie_invalid_value() {
+        Cookie::new("id", "a;b");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cookie attribute")]
+    fn test_cookie_invalid_attribute() {
+        Cookie::new("id", "a3fWa").path("/; Domain=evil.example");
+    }
+
+    #[test]
+    fn test_cookie_jar() {
+        let mut jar = CookieJar::new();
+        assert!(jar.is_empty());
+        jar.add("theme", "light");
+        jar.add("id", "a3fWa").path("/").http_only(true);
+        jar.add("theme", "dark");
+        jar.remove("old").path("/");
+
+        let mut headers = HeaderMap::new();
+        jar.apply(&mut headers);
+        let set_cookies = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            set_cookies,
+            vec![
+                "id=a3fWa; Path=/; HttpOnly",
+                "theme=dark",
+                "old=; Path=/; Max-Age=0",
+            ],
+        );
+    }
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::fmt;
 
+use crate::routing::RouteInfo;
+
 pub trait ServiceError: std::error::Error + Any + Send + Sync {
     fn status(&self) -> StatusCode;
     fn class_name(&self) -> &str;
@@ -40,6 +42,9 @@ pub enum NailsError {
     JsonBodyError(JsonBodyError),
     BodyError(BodyError),
     QueryError(QueryError),
+    HeaderError(HeaderError),
+    CookieError(CookieError),
+    SessionError(SessionError),
     AnyError(Box<dyn ServiceError>),
 }
 
@@ -70,6 +75,9 @@ impl ServiceError for NailsError {
             JsonBodyError(e) => e.status(),
             BodyError(e) => e.status(),
             QueryError(e) => e.status(),
+            HeaderError(e) => e.status(),
+            CookieError(e) => e.status(),
+            SessionError(e) => e.status(),
             AnyError(e) => e.status(),
         }
     }
@@ -80,6 +88,9 @@ impl ServiceError for NailsError {
             JsonBodyError(e) => e.class_name(),
             BodyError(e) => e.class_name(),
             QueryError(e) => e.class_name(),
+            HeaderError(e) => e.class_name(),
+            CookieError(e) => e.class_name(),
+            SessionError(e) => e.class_name(),
             AnyError(e) => e.class_name(),
         }
     }
@@ -90,6 +101,9 @@ impl ServiceError for NailsError {
             JsonBodyError(e) => e.has_public_message(),
             BodyError(e) => e.has_public_message(),
             QueryError(e) => e.has_public_message(),
+            HeaderError(e) => e.has_public_message(),
+            CookieError(e) => e.has_public_message(),
+            SessionError(e) => e.has_public_message(),
             AnyError(e) => e.has_public_message(),
         }
     }
@@ -100,6 +114,9 @@ impl ServiceError for NailsError {
             JsonBodyError(e) => e.fmt_public_message(f),
             BodyError(e) => e.fmt_public_message(f),
             QueryError(e) => e.fmt_public_message(f),
+            HeaderError(e) => e.fmt_public_message(f),
+            CookieError(e) => e.fmt_public_message(f),
+            SessionError(e) => e.fmt_public_message(f),
             AnyError(e) => e.fmt_public_message(f),
         }
     }
@@ -113,6 +130,9 @@ impl std::error::Error for NailsError {
             JsonBodyError(e) => e.description(),
             BodyError(e) => e.description(),
             QueryError(e) => e.description(),
+            HeaderError(e) => e.description(),
+            CookieError(e) => e.description(),
+            SessionError(e) => e.description(),
             AnyError(e) => e.description(),
         }
     }
@@ -124,6 +144,9 @@ impl std::error::Error for NailsError {
             JsonBodyError(e) => e.source(),
             BodyError(e) => e.source(),
             QueryError(e) => e.source(),
+            HeaderError(e) => e.source(),
+            CookieError(e) => e.source(),
+            SessionError(e) => e.source(),
             AnyError(e) => e.source(),
         }
     }
@@ -137,6 +160,9 @@ impl fmt::Display for NailsError {
             JsonBodyError(e) => e.fmt(f),
             BodyError(e) => e.fmt(f),
             QueryError(e) => e.fmt(f),
+            HeaderError(e) => e.fmt(f),
+            CookieError(e) => e.fmt(f),
+            SessionError(e) => e.fmt(f),
             AnyError(e) => e.fmt(f),
         }
     }
@@ -160,6 +186,24 @@ impl From<QueryError> for NailsError {
     }
 }
 
+impl From<HeaderError> for NailsError {
+    fn from(e: HeaderError) -> Self {
+        NailsError::HeaderError(e)
+    }
+}
+
+impl From<CookieError> for NailsError {
+    fn from(e: CookieError) -> Self {
+        NailsError::CookieError(e)
+    }
+}
+
+impl From<SessionError> for NailsError {
+    fn from(e: SessionError) -> Self {
+        NailsError::SessionError(e)
+    }
+}
+
 impl From<BodyError> for NailsError {
     fn from(e: BodyError) -> Self {
         NailsError::BodyError(e)
@@ -358,3 +402,175 @@ impl From<std::num::ParseFloatError> for QueryError {
         QueryError::ParseFloatError(e)
     }
 }
+
+impl serde::de::Error for QueryError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        QueryError::AnyError(failure::err_msg(msg.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum HeaderError {
+    MultipleHeader,
+    NoHeader,
+    ToStrError(hyper::header::ToStrError),
+    ParseIntError(std::num::ParseIntError),
+    AnyError(failure::Error),
+}
+
+impl ServiceError for HeaderError {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+    fn class_name(&self) -> &str {
+        "nails::error::HeaderError"
+    }
+    fn has_public_message(&self) -> bool {
+        true
+    }
+    fn fmt_public_message(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use HeaderError::*;
+        match self {
+            MultipleHeader => write!(f, "multiple header values found"),
+            NoHeader => write!(f, "no header value found"),
+            ToStrError(e) => write!(f, "{}", e),
+            ParseIntError(e) => write!(f, "{}", e),
+            AnyError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use HeaderError::*;
+        match self {
+            ToStrError(e) => Some(e),
+            ParseIntError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<hyper::header::ToStrError> for HeaderError {
+    fn from(e: hyper::header::ToStrError) -> Self {
+        HeaderError::ToStrError(e)
+    }
+}
+
+impl From<std::num::ParseIntError> for HeaderError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        HeaderError::ParseIntError(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum CookieError {
+    MultipleCookie,
+    NoCookie,
+    ParseIntError(std::num::ParseIntError),
+    AnyError(failure::Error),
+}
+
+impl ServiceError for CookieError {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+    fn class_name(&self) -> &str {
+        "nails::error::CookieError"
+    }
+    fn has_public_message(&self) -> bool {
+        true
+    }
+    fn fmt_public_message(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for CookieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CookieError::*;
+        match self {
+            MultipleCookie => write!(f, "multiple cookie values found"),
+            NoCookie => write!(f, "no cookie value found"),
+            ParseIntError(e) => write!(f, "{}", e),
+            AnyError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CookieError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use CookieError::*;
+        match self {
+            ParseIntError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for CookieError {
+    fn from(e: std::num::ParseIntError) -> Self {
+        CookieError::ParseIntError(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    /// A session was requested but the service has no session store.
+    NotConfigured,
+    StoreError(failure::Error),
+}
+
+impl ServiceError for SessionError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+    fn class_name(&self) -> &str {
+        "nails::error::SessionError"
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SessionError::*;
+        match self {
+            NotConfigured => write!(f, "sessions are not configured for this service"),
+            StoreError(e) => write!(f, "session store error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+#[derive(Debug, Clone)]
+pub struct RouteConflict {
+    pub first: RouteInfo,
+    pub second: RouteInfo,
+}
+
+#[derive(Debug, Clone)]
+pub struct RouteConflictError {
+    pub conflicts: Vec<RouteConflict>,
+}
+
+impl fmt::Display for RouteConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ambiguous routes:")?;
+        for conflict in &self.conflicts {
+            write!(f, "\n    {} and {}", conflict.first, conflict.second)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RouteConflictError {
+    fn description(&self) -> &str {
+        "ambiguous routes"
+    }
+}
@@ -1,13 +1,16 @@
 extern crate self as nails;
 
-pub use request::Preroute;
+pub use request::{Preroute, RequestHead, ToUri};
 pub use routing::{Routable, Router};
 pub use service::Service;
 
 #[doc(hidden)]
 pub mod __rt;
+pub mod cookie;
 pub mod error;
+pub mod query;
 pub mod request;
 pub mod routing;
 pub mod service;
+pub mod session;
 pub mod utils;
@@ -0,0 +1,628 @@
+use std::collections::{btree_map, BTreeMap};
+use std::mem;
+use std::slice;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::QueryError;
+use crate::request::parse_query_pairs;
+
+/// A query value decoded with the Rails-like bracket syntax.
+///
+/// `a=1` gives a string, `a[]=1&a[]=2` (or repeating `a=`) gives an array, and `a[b]=1` gives a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryValue {
+    String(String),
+    Array(Vec<QueryValue>),
+    Map(BTreeMap<String, QueryValue>),
+}
+
+/// The maximum number of brackets in a key, like Rack's `param_depth_limit`.
+///
+/// Both decoding and dropping the tree recurse once per level.
+const MAX_DEPTH: usize = 32;
+
+/// Decodes a query string into a tree, like `parse_query` but honoring brackets in keys.
+///
+/// Pairs which conflict with the structure built so far (e.g. `a=1&a[b]=2`) are ignored.
+/// Keys nested deeper than 32 levels are taken literally.
+pub fn parse_nested_query(query: &str) -> BTreeMap<String, QueryValue> {
+    let mut tree = BTreeMap::new();
+    for (key, value) in parse_query_pairs(query) {
+        let segments = if let Some(segments) = split_key(&key) {
+            segments
+        } else {
+            vec![key.as_str()]
+        };
+        insert_nested(&mut tree, &segments, value);
+    }
+    tree
+}
+
+// `a[b][]` -> `["a", "b", ""]`
+fn split_key(key: &str) -> Option<Vec<&str>> {
+    let bracket = key.find('[')?;
+    if bracket == 0 {
+        return None;
+    }
+    let mut segments = vec![&key[..bracket]];
+    let mut rest = &key[bracket..];
+    while !rest.is_empty() {
+        if segments.len() > MAX_DEPTH {
+            return None;
+        }
+        if !rest.starts_with('[') {
+            return None;
+        }
+        let close = rest.find(']')?;
+        let segment = &rest[1..close];
+        if segment.contains('[') {
+            return None;
+        }
+        segments.push(segment);
+        rest = &rest[close + 1..];
+    }
+    Some(segments)
+}
+
+fn insert_nested(map: &mut BTreeMap<String, QueryValue>, segments: &[&str], value: String) {
+    let (name, rest) = (segments[0], &segments[1..]);
+    if rest.is_empty() {
+        match map.entry(name.to_owned()) {
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(QueryValue::String(value));
+            }
+            btree_map::Entry::Occupied(mut entry) => {
+                let entry = entry.get_mut();
+                match entry {
+                    QueryValue::String(_) => {
+                        let old = mem::replace(entry, QueryValue::Array(Vec::new()));
+                        *entry = QueryValue::Array(vec![old, QueryValue::String(value)]);
+                    }
+                    QueryValue::Array(array) => array.push(QueryValue::String(value)),
+                    QueryValue::Map(_) => {}
+                }
+            }
+        }
+        return;
+    }
+    let entry = map.entry(name.to_owned()).or_insert_with(|| {
+        if rest[0].is_empty() {
+            QueryValue::Array(Vec::new())
+        } else {
+            QueryValue::Map(BTreeMap::new())
+        }
+    });
+    match entry {
+        QueryValue::Array(array) if rest[0].is_empty() => {
+            if rest.len() == 1 {
+                array.push(QueryValue::String(value));
+                return;
+            }
+            // `a[][b]=1&a[][c]=2&a[][b]=3` gives two maps: a new one is started when the key is already taken.
+            let reuse_last = match array.last() {
+                Some(QueryValue::Map(last)) => !last.contains_key(rest[1]),
+                _ => false,
+            };
+            if !reuse_last {
+                array.push(QueryValue::Map(BTreeMap::new()));
+            }
+            if let Some(QueryValue::Map(last)) = array.last_mut() {
+                insert_nested(last, &rest[1..], value);
+            }
+        }
+        QueryValue::Map(map) if !rest[0].is_empty() => insert_nested(map, rest, value),
+        _ => {}
+    }
+}
+
+/// A query parameter decoded with serde from the bracket syntax, e.g. `filter[author]=jake&filter[tags][]=rust`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Query<T>(pub T);
+
+pub trait FromQueryStruct: Sized {
+    fn from_query_struct(value: Option<&QueryValue>) -> Result<Self, QueryError>;
+}
+
+impl<T> FromQueryStruct for Query<T>
+where
+    T: DeserializeOwned,
+{
+    fn from_query_struct(value: Option<&QueryValue>) -> Result<Self, QueryError> {
+        // A missing parameter is read as an empty map so that structs with optional fields can be decoded.
+        let empty = QueryValue::Map(BTreeMap::new());
+        let value = value.unwrap_or(&empty);
+        Ok(Query(T::deserialize(ValueDeserializer(value))?))
+    }
+}
+
+impl<T> FromQueryStruct for Option<Query<T>>
+where
+    T: DeserializeOwned,
+{
+    fn from_query_struct(value: Option<&QueryValue>) -> Result<Self, QueryError> {
+        if let Some(value) = value {
+            Ok(Some(Query::from_query_struct(Some(value))?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub trait ToQueryStruct {
+    /// Returns the query pairs under `name`, before percent-encoding.
+    fn to_query_struct(&self, name: &str) -> Vec<(String, String)>;
+}
+
+impl<T> ToQueryStruct for Query<T>
+where
+    T: Serialize,
+{
+    fn to_query_struct(&self, name: &str) -> Vec<(String, String)> {
+        let value = serde_json::to_value(&self.0).expect("failed to serialize query");
+        let mut pairs = Vec::new();
+        flatten_value(name.to_owned(), &value, &mut pairs);
+        pairs
+    }
+}
+
+impl<T> ToQueryStruct for Option<Query<T>>
+where
+    T: Serialize,
+{
+    fn to_query_struct(&self, name: &str) -> Vec<(String, String)> {
+        if let Some(value) = self {
+            value.to_query_struct(name)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn flatten_value(key: String, value: &Value, pairs: &mut Vec<(String, String)>) {
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => pairs.push((key, b.to_string())),
+        Value::Number(n) => pairs.push((key, n.to_string())),
+        Value::String(s) => pairs.push((key, s.clone())),
+        Value::Array(array) => {
+            for elem in array {
+                flatten_value(format!("{}[]", key), elem, pairs);
+            }
+        }
+        Value::Object(object) => {
+            for (name, elem) in object {
+                flatten_value(format!("{}[{}]", key, name), elem, pairs);
+            }
+        }
+    }
+}
+
+struct ValueDeserializer<'a>(&'a QueryValue);
+
+impl<'a> ValueDeserializer<'a> {
+    fn as_str(&self) -> Result<&'a str, QueryError> {
+        match self.0 {
+            QueryValue::String(s) => Ok(s),
+            QueryValue::Array(_) => Err(QueryError::MultipleQuery),
+            QueryValue::Map(_) => Err(de::Error::invalid_type(Unexpected::Map, &"a string")),
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($method:ident, $visit:ident) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+            visitor.$visit(self.as_str()?.parse()?)
+        }
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = QueryError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.0 {
+            QueryValue::String(s) => visitor.visit_str(s),
+            QueryValue::Array(array) => visitor.visit_seq(SeqDeserializer(array.iter())),
+            QueryValue::Map(map) => visitor.visit_map(MapDeserializer {
+                iter: map.iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.as_str()? {
+            "true" => visitor.visit_bool(true),
+            "false" => visitor.visit_bool(false),
+            s => Err(de::Error::invalid_value(Unexpected::Str(s), &"a boolean")),
+        }
+    }
+
+    deserialize_parsed!(deserialize_i8, visit_i8);
+    deserialize_parsed!(deserialize_i16, visit_i16);
+    deserialize_parsed!(deserialize_i32, visit_i32);
+    deserialize_parsed!(deserialize_i64, visit_i64);
+    deserialize_parsed!(deserialize_i128, visit_i128);
+    deserialize_parsed!(deserialize_u8, visit_u8);
+    deserialize_parsed!(deserialize_u16, visit_u16);
+    deserialize_parsed!(deserialize_u32, visit_u32);
+    deserialize_parsed!(deserialize_u64, visit_u64);
+    deserialize_parsed!(deserialize_u128, visit_u128);
+    deserialize_parsed!(deserialize_f32, visit_f32);
+    deserialize_parsed!(deserialize_f64, visit_f64);
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        let s = self.as_str()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(de::Error::invalid_value(Unexpected::Str(s), &"a character")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_str(self.as_str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_bytes(self.as_str()?.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // Absent keys are handled by serde as missing fields.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // A single value is read as a one-element sequence, as in `FromQuery for Vec<T>`.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.0 {
+            QueryValue::String(_) => {
+                visitor.visit_seq(SeqDeserializer(slice::from_ref(self.0).iter()))
+            }
+            QueryValue::Array(array) => visitor.visit_seq(SeqDeserializer(array.iter())),
+            QueryValue::Map(_) => Err(de::Error::invalid_type(Unexpected::Map, &"a sequence")),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        match self.0 {
+            QueryValue::Map(map) => visitor.visit_map(MapDeserializer {
+                iter: map.iter(),
+                value: None,
+            }),
+            QueryValue::String(s) => Err(de::Error::invalid_type(Unexpected::Str(s), &"a map")),
+            QueryValue::Array(_) => Err(de::Error::invalid_type(Unexpected::Seq, &"a map")),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        self.deserialize_map(visitor)
+    }
+
+    // Only unit variants can be written in a query.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, QueryError> {
+        visitor.visit_enum(self.as_str()?.into_deserializer())
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqDeserializer<'a>(slice::Iter<'a, QueryValue>);
+
+impl<'de, 'a> de::SeqAccess<'de> for SeqDeserializer<'a> {
+    type Error = QueryError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, QueryError> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapDeserializer<'a> {
+    iter: btree_map::Iter<'a, String, QueryValue>,
+    value: Option<&'a QueryValue>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for MapDeserializer<'a> {
+    type Error = QueryError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, QueryError> {
+        if let Some((key, value)) = self.iter.next() {
+            self.value = Some(value);
+            // Keys go through `ValueDeserializer` as well so that e.g. `HashMap<u32, _>` works.
+            let key = QueryValue::String(key.clone());
+            seed.deserialize(ValueDeserializer(&key)).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, QueryError> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[allow(non_snake_case)]
+    fn S(s: &'static str) -> QueryValue {
+        QueryValue::String(s.to_owned())
+    }
+
+    macro_rules! map {
+        ($($e:expr),*) => {
+            vec![$({ let (k, v) = $e; (k.to_owned(), v) },)*]
+                .into_iter()
+                .collect::<BTreeMap<String, QueryValue>>()
+        };
+        ($($e:expr,)*) => {
+            map![$($e),*]
+        };
+    }
+
+    #[test]
+    fn test_parse_nested_query() {
+        assert_eq!(parse_nested_query(""), map![]);
+        assert_eq!(
+            parse_nested_query("a=1&b=2&b=3"),
+            map![
+                ("a", S("1")),
+                ("b", QueryValue::Array(vec![S("2"), S("3")]))
+            ],
+        );
+        assert_eq!(
+            parse_nested_query("filter[author]=jake&filter[tags][]=a&filter[tags][]=b"),
+            map![(
+                "filter",
+                QueryValue::Map(map![
+                    ("author", S("jake")),
+                    ("tags", QueryValue::Array(vec![S("a"), S("b")])),
+                ]),
+            )],
+        );
+        assert_eq!(
+            parse_nested_query("%66ilter%5Bauthor%5D=jake"),
+            map![("filter", QueryValue::Map(map![("author", S("jake"))]))],
+        );
+        assert_eq!(
+            parse_nested_query("items[][name]=a&items[][id]=1&items[][name]=b"),
+            map![(
+                "items",
+                QueryValue::Array(vec![
+                    QueryValue::Map(map![("name", S("a")), ("id", S("1"))]),
+                    QueryValue::Map(map![("name", S("b"))]),
+                ]),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_query_illformed() {
+        assert_eq!(
+            parse_nested_query("a[b=1&[c]=2&d]=3&e[f]g=4&h[[i]]=5"),
+            map![
+                ("a[b", S("1")),
+                ("[c]", S("2")),
+                ("d]", S("3")),
+                ("e[f]g", S("4")),
+                ("h[[i]]", S("5")),
+            ],
+        );
+        assert_eq!(
+            parse_nested_query("a=1&a[b]=2&c[d]=3&c=4&c[]=5"),
+            map![("a", S("1")), ("c", QueryValue::Map(map![("d", S("3"))]))],
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_query_depth() {
+        let key = |depth: usize| format!("a{}", "[b]".repeat(depth));
+        let mut value = S("1");
+        for _ in 0..MAX_DEPTH {
+            value = QueryValue::Map(map![("b", value)]);
+        }
+        assert_eq!(
+            parse_nested_query(&format!("{}=1", key(MAX_DEPTH))),
+            map![("a", value)],
+        );
+        assert_eq!(
+            parse_nested_query(&format!("{}=1", key(MAX_DEPTH + 1))),
+            map![(key(MAX_DEPTH + 1), S("1"))],
+        );
+        let long_key = key(20000);
+        assert_eq!(
+            parse_nested_query(&format!("{}=1", long_key)),
+            map![(long_key, S("1"))],
+        );
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Filter {
+        author: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        limit: Option<u32>,
+        order: Option<Order>,
+        favorited: Option<bool>,
+    }
+
+    fn filter(query: &str) -> Result<Filter, QueryError> {
+        let tree = parse_nested_query(query);
+        Query::from_query_struct(tree.get("filter")).map(|Query(filter)| filter)
+    }
+
+    #[test]
+    fn test_from_query_struct() {
+        assert_eq!(
+            filter("filter[author]=jake&filter[tags][]=a&filter[tags][]=b&filter[limit]=20")
+                .unwrap(),
+            Filter {
+                author: Some("jake".to_owned()),
+                tags: vec!["a".to_owned(), "b".to_owned()],
+                limit: Some(20),
+                order: None,
+                favorited: None,
+            },
+        );
+        assert_eq!(
+            filter("filter[tags]=a&filter[order]=desc&filter[favorited]=true").unwrap(),
+            Filter {
+                author: None,
+                tags: vec!["a".to_owned()],
+                limit: None,
+                order: Some(Order::Desc),
+                favorited: Some(true),
+            },
+        );
+        assert_eq!(
+            filter("").unwrap(),
+            Filter {
+                author: None,
+                tags: vec![],
+                limit: None,
+                order: None,
+                favorited: None,
+            },
+        );
+        assert!(filter("filter[limit]=x").is_err());
+        assert!(filter("filter[order]=random").is_err());
+        assert!(filter("filter[author]=a&filter[author]=b").is_err());
+        assert!(filter("filter[author][name]=jake").is_err());
+        assert!(filter("filter=jake").is_err());
+
+        let tree = parse_nested_query("");
+        assert_eq!(
+            Option::<Query<Filter>>::from_query_struct(tree.get("filter")).unwrap(),
+            None,
+        );
+
+        let tree = parse_nested_query("counts[1]=10&counts[2]=20");
+        let Query(counts) =
+            Query::<HashMap<u32, u64>>::from_query_struct(tree.get("counts")).unwrap();
+        assert_eq!(counts, vec![(1, 10), (2, 20)].into_iter().collect());
+    }
+
+    #[test]
+    fn test_to_query_struct() {
+        let query = Query(Filter {
+            author: Some("jake".to_owned()),
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            limit: Some(20),
+            order: Some(Order::Asc),
+            favorited: None,
+        });
+        let pairs = query.to_query_struct("filter");
+        assert_eq!(
+            pairs,
+            vec![
+                ("filter[author]".to_owned(), "jake".to_owned()),
+                ("filter[limit]".to_owned(), "20".to_owned()),
+                ("filter[order]".to_owned(), "asc".to_owned()),
+                ("filter[tags][]".to_owned(), "a".to_owned()),
+                ("filter[tags][]".to_owned(), "b".to_owned()),
+            ],
+        );
+        assert_eq!(None::<Query<Filter>>.to_query_struct("filter"), vec![]);
+    }
+}
@@ -1,12 +1,19 @@
+use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::slice;
 
 use async_trait::async_trait;
-use hyper::{Body, Method, Request};
+use hyper::header::HeaderValue;
+use hyper::header::{COOKIE, HOST};
+use hyper::{Body, HeaderMap, Method, Request, Uri};
+use once_cell::sync::OnceCell;
+use regex::Regex;
 use serde::de::DeserializeOwned;
 
-use crate::error::{BodyError, ContentTypeError, JsonBodyError, NailsError, QueryError};
+use crate::error::{
+    BodyError, ContentTypeError, CookieError, HeaderError, JsonBodyError, NailsError, QueryError,
+};
 
 pub use nails_derive::Preroute;
 
@@ -15,12 +22,142 @@ pub trait Preroute: Sized {
     fn path_prefix_hint() -> &'static str {
         ""
     }
+    fn route_patterns() -> Vec<RoutePattern> {
+        Vec::new()
+    }
     fn match_path(method: &Method, path: &str) -> bool;
+    /// Checks the rest of the request head, such as the host.
+    fn match_head(_head: &RequestHead<'_>) -> bool {
+        true
+    }
+    /// Returns true if the route matching the HEAD request lists HEAD in its `method`.
+    ///
+    /// Such routes answer HEAD themselves; others are run as GET with the body dropped.
+    fn explicit_head(_head: &RequestHead<'_>) -> bool {
+        false
+    }
+    /// Returns the methods accepted for `path`, or an empty list if the path doesn't match.
+    fn allowed_methods(_path: &str) -> Vec<Method> {
+        Vec::new()
+    }
 
     // TODO: Request<Body> -> RoutableRequest
     async fn from_request(req: Request<Body>) -> Result<Self, NailsError>;
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePattern {
+    pub methods: Vec<Method>,
+    pub host: Option<&'static str>,
+    pub path: &'static str,
+}
+
+/// The parts of a request routes are matched against.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestHead<'a> {
+    method: &'a Method,
+    path: &'a str,
+    host: Option<&'a str>,
+    scheme: Option<&'a str>,
+    headers: Option<&'a HeaderMap>,
+}
+
+impl<'a> RequestHead<'a> {
+    /// Creates a head without a host or headers.
+    pub fn new(method: &'a Method, path: &'a str) -> Self {
+        Self {
+            method,
+            path,
+            host: None,
+            scheme: None,
+            headers: None,
+        }
+    }
+
+    pub fn from_request<B>(req: &'a Request<B>) -> Self {
+        Self {
+            method: req.method(),
+            path: req.uri().path(),
+            host: request_host(req),
+            scheme: req.uri().scheme_str(),
+            headers: Some(req.headers()),
+        }
+    }
+
+    pub fn with_path(self, path: &'a str) -> Self {
+        Self { path, ..self }
+    }
+
+    pub fn with_host(self, host: Option<&'a str>) -> Self {
+        Self { host, ..self }
+    }
+
+    pub fn method(&self) -> &'a Method {
+        self.method
+    }
+
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// The host name without the port. See [`request_host`](request_host).
+    pub fn host(&self) -> Option<&'a str> {
+        self.host
+    }
+
+    /// The scheme, known only if the request target is in absolute form.
+    ///
+    /// Routes never match on it: behind a TLS terminator the scheme is not known to the server,
+    /// so handlers which care must check it (or a forwarding header) themselves.
+    pub fn scheme(&self) -> Option<&'a str> {
+        self.scheme
+    }
+
+    pub fn headers(&self) -> Option<&'a HeaderMap> {
+        self.headers
+    }
+}
+
+/// Returns the host name the request is sent to, without the port.
+///
+/// It is taken from the request target if it's in absolute form, and from the `Host` header otherwise.
+pub fn request_host<B>(req: &Request<B>) -> Option<&str> {
+    let host = if let Some(host) = req.uri().host() {
+        host
+    } else {
+        req.headers().get(HOST)?.to_str().ok()?
+    };
+    let host = if host.starts_with('[') {
+        // IPv6 address
+        &host[..host.find(']').map_or(host.len(), |pos| pos + 1)]
+    } else {
+        host.split(':').next().unwrap()
+    };
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// Splits `host` into labels if they match `pattern`, where `None` matches any label.
+///
+/// Literal labels are compared case-insensitively.
+pub fn match_host<'a>(host: &'a str, pattern: &[Option<&str>]) -> Option<Vec<&'a str>> {
+    let labels = host.split('.').collect::<Vec<_>>();
+    let matches = labels.len() == pattern.len()
+        && labels.iter().zip(pattern).all(|(label, pat)| match pat {
+            Some(literal) => label.eq_ignore_ascii_case(literal),
+            None => !label.is_empty(),
+        });
+    if matches {
+        Some(labels)
+    } else {
+        None
+    }
+}
+
 #[async_trait]
 pub trait FromBody: Sized {
     async fn from_body(req: Request<Body>) -> Result<Self, NailsError>;
@@ -63,11 +200,65 @@ where
 }
 
 pub trait FromPath: Sized {
+    /// Parses a percent-decoded path component.
     fn from_path(path_component: &str) -> Result<Self, ()>;
 
     fn matches(path_component: &str) -> bool {
         Self::from_path(path_component).is_ok()
     }
+
+    /// Parses a path component as it appears in the URI.
+    ///
+    /// Components containing an encoded slash (`%2F`) are rejected
+    /// unless `allow_encoded_slash` is set.
+    fn from_raw_path(raw_component: &str, allow_encoded_slash: bool) -> Result<Self, ()> {
+        Self::from_path(&decode_path_component(raw_component, allow_encoded_slash)?)
+    }
+
+    fn matches_raw(raw_component: &str, allow_encoded_slash: bool) -> bool {
+        decode_path_component(raw_component, allow_encoded_slash)
+            .map(|component| Self::matches(&component))
+            .unwrap_or(false)
+    }
+}
+
+/// A path component as it appears in the URI, without percent-decoding.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct RawPathSegment(pub String);
+
+impl FromPath for RawPathSegment {
+    fn from_path(path_component: &str) -> Result<Self, ()> {
+        Ok(RawPathSegment(encode_path_component(path_component)))
+    }
+    fn matches(_path_component: &str) -> bool {
+        true
+    }
+    fn from_raw_path(raw_component: &str, _allow_encoded_slash: bool) -> Result<Self, ()> {
+        Ok(RawPathSegment(raw_component.to_owned()))
+    }
+    fn matches_raw(_raw_component: &str, _allow_encoded_slash: bool) -> bool {
+        true
+    }
+}
+
+impl ToPath for RawPathSegment {
+    fn to_path(&self) -> String {
+        parse_percent_encoding(&self.0).unwrap_or_else(|| self.0.clone())
+    }
+    fn to_raw_path(&self) -> String {
+        self.0.clone()
+    }
+}
+
+pub fn decode_path_component(raw_component: &str, allow_encoded_slash: bool) -> Result<String, ()> {
+    let has_encoded_slash = raw_component
+        .as_bytes()
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1] == b'2' && (w[2] == b'f' || w[2] == b'F'));
+    if has_encoded_slash && !allow_encoded_slash {
+        return Err(());
+    }
+    parse_percent_encoding(raw_component).ok_or(())
 }
 
 impl FromPath for String {
@@ -92,6 +283,258 @@ macro_rules! from_path_int_matcher {
 }
 from_path_int_matcher!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
 
+pub trait ToPath {
+    /// Returns the path component, before percent-encoding.
+    fn to_path(&self) -> String;
+
+    /// Returns the path component as it appears in the URI.
+    fn to_raw_path(&self) -> String {
+        encode_path_component(&self.to_path())
+    }
+}
+
+impl ToPath for String {
+    fn to_path(&self) -> String {
+        self.clone()
+    }
+}
+
+macro_rules! to_path_int {
+    ($($int:ty)*) => {
+        $(
+            impl ToPath for $int {
+                fn to_path(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+to_path_int!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
+
+/// Splits a path component like `archive.tar.gz` along a pattern like `{name}.{ext}`.
+///
+/// `literals` are the strings around the variables, so there is one value less than them.
+/// When a separator occurs several times, later variables take the shortest match:
+/// the example gives `archive.tar` and `gz`.
+pub fn split_path_component<'a>(component: &'a str, literals: &[&str]) -> Option<Vec<&'a str>> {
+    let (first, literals) = literals.split_first()?;
+    let (last, separators) = literals.split_last()?;
+    let mut rest = component.strip_prefix(first)?.strip_suffix(last)?;
+    let mut values = Vec::with_capacity(separators.len() + 1);
+    for separator in separators.iter().rev() {
+        let pos = rest.rfind(separator)?;
+        values.push(&rest[pos + separator.len()..]);
+        rest = &rest[..pos];
+    }
+    values.push(rest);
+    values.reverse();
+    Some(values)
+}
+
+/// A constraint on a path variable, like `{id:\d+}` or `{slug:!feed}`.
+///
+/// The regex is compiled on first use.
+#[derive(Debug)]
+pub struct PathConstraint {
+    pattern: Cow<'static, str>,
+    negated: bool,
+    regex: OnceCell<Regex>,
+}
+
+impl PathConstraint {
+    /// Creates a constraint from an anchored regex like `^(?:\d+)$`.
+    pub const fn new(pattern: &'static str, negated: bool) -> Self {
+        Self {
+            pattern: Cow::Borrowed(pattern),
+            negated,
+            regex: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn from_parsed(constraint: nails_pattern::Constraint) -> Self {
+        Self {
+            pattern: Cow::Owned(constraint.pattern),
+            negated: constraint.negated,
+            regex: OnceCell::new(),
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        let regex = self
+            .regex
+            .get_or_init(|| Regex::new(&self.pattern).expect("invalid path constraint"));
+        regex.is_match(value) != self.negated
+    }
+
+    /// Checks a value as it appears in the URI, after percent-decoding.
+    ///
+    /// Values containing an encoded slash (`%2F`) are rejected
+    /// unless `allow_encoded_slash` is set, as the variable's field would.
+    pub fn matches_raw(&self, raw_value: &str, allow_encoded_slash: bool) -> bool {
+        decode_path_component(raw_value, allow_encoded_slash)
+            .map(|value| self.matches(&value))
+            .unwrap_or(false)
+    }
+}
+
+/// Extracts the rest of the path, bound by a catch-all variable like `{*rest}`.
+///
+/// The input doesn't contain the leading slash and may be empty.
+/// `String` and `Vec<T>` reject `.` and `..` segments, even percent-encoded ones,
+/// so that the rest can be joined to a directory safely. Use `RawPathRest` to receive them.
+pub trait FromPathRest: Sized {
+    /// Parses the percent-decoded rest of the path.
+    fn from_path_rest(path_rest: &str) -> Result<Self, ()>;
+
+    fn matches_rest(path_rest: &str) -> bool {
+        Self::from_path_rest(path_rest).is_ok()
+    }
+
+    /// Parses the rest of the path as it appears in the URI.
+    fn from_raw_path_rest(raw_rest: &str, allow_encoded_slash: bool) -> Result<Self, ()> {
+        Self::from_path_rest(&decode_path_component(raw_rest, allow_encoded_slash)?)
+    }
+
+    fn matches_raw_rest(raw_rest: &str, allow_encoded_slash: bool) -> bool {
+        decode_path_component(raw_rest, allow_encoded_slash)
+            .map(|rest| Self::matches_rest(&rest))
+            .unwrap_or(false)
+    }
+}
+
+impl FromPathRest for String {
+    fn from_path_rest(path_rest: &str) -> Result<Self, ()> {
+        if path_rest.split('/').any(is_dot_segment) {
+            return Err(());
+        }
+        Ok(path_rest.to_owned())
+    }
+}
+
+impl<T> FromPathRest for Vec<T>
+where
+    T: FromPath,
+{
+    fn from_path_rest(path_rest: &str) -> Result<Self, ()> {
+        if path_rest.is_empty() {
+            return Ok(Vec::new());
+        }
+        path_rest
+            .split('/')
+            .map(|component| {
+                if is_dot_segment(component) {
+                    return Err(());
+                }
+                T::from_path(component)
+            })
+            .collect()
+    }
+    fn matches_rest(path_rest: &str) -> bool {
+        path_rest.is_empty()
+            || path_rest
+                .split('/')
+                .all(|component| !is_dot_segment(component) && T::matches(component))
+    }
+    // Split before decoding, so that an encoded slash stays in its component.
+    fn from_raw_path_rest(raw_rest: &str, allow_encoded_slash: bool) -> Result<Self, ()> {
+        if raw_rest.is_empty() {
+            return Ok(Vec::new());
+        }
+        raw_rest
+            .split('/')
+            .map(|component| {
+                if is_raw_dot_segment(component) {
+                    return Err(());
+                }
+                T::from_raw_path(component, allow_encoded_slash)
+            })
+            .collect()
+    }
+    fn matches_raw_rest(raw_rest: &str, allow_encoded_slash: bool) -> bool {
+        raw_rest.is_empty()
+            || raw_rest.split('/').all(|component| {
+                !is_raw_dot_segment(component) && T::matches_raw(component, allow_encoded_slash)
+            })
+    }
+}
+
+/// The rest of the path as it appears in the URI, without percent-decoding or rejecting `.` and `..`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct RawPathRest(pub String);
+
+impl FromPathRest for RawPathRest {
+    fn from_path_rest(path_rest: &str) -> Result<Self, ()> {
+        Ok(RawPathRest(encode_path_rest(
+            &path_rest.split('/').map(str::to_owned).collect::<Vec<_>>(),
+        )))
+    }
+    fn matches_rest(_path_rest: &str) -> bool {
+        true
+    }
+    fn from_raw_path_rest(raw_rest: &str, _allow_encoded_slash: bool) -> Result<Self, ()> {
+        Ok(RawPathRest(raw_rest.to_owned()))
+    }
+    fn matches_raw_rest(_raw_rest: &str, _allow_encoded_slash: bool) -> bool {
+        true
+    }
+}
+
+fn is_dot_segment(component: &str) -> bool {
+    component == "." || component == ".."
+}
+
+fn is_raw_dot_segment(raw_component: &str) -> bool {
+    parse_percent_encoding(raw_component)
+        .map(|component| is_dot_segment(&component))
+        .unwrap_or(false)
+}
+
+pub trait ToPathRest {
+    /// Returns the path components, before percent-encoding.
+    fn to_path_rest(&self) -> Vec<String>;
+
+    /// Returns the rest of the path as it appears in the URI.
+    fn to_raw_path_rest(&self) -> String {
+        encode_path_rest(&self.to_path_rest())
+    }
+}
+
+impl ToPathRest for String {
+    fn to_path_rest(&self) -> Vec<String> {
+        self.split('/').map(str::to_owned).collect()
+    }
+}
+
+impl ToPathRest for RawPathRest {
+    fn to_path_rest(&self) -> Vec<String> {
+        self.0
+            .split('/')
+            .map(|component| {
+                parse_percent_encoding(component).unwrap_or_else(|| component.to_owned())
+            })
+            .collect()
+    }
+    fn to_raw_path_rest(&self) -> String {
+        self.0.clone()
+    }
+}
+
+impl<T> ToPathRest for Vec<T>
+where
+    T: ToPath,
+{
+    fn to_path_rest(&self) -> Vec<String> {
+        self.iter().map(T::to_path).collect()
+    }
+    fn to_raw_path_rest(&self) -> String {
+        self.iter()
+            .map(T::to_raw_path)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
 pub trait FromQuery: Sized {
     // TODO: Result
     fn from_query(values: &[String]) -> Result<Self, QueryError>;
@@ -159,18 +602,260 @@ impl_int_from_query!(u64);
 impl_int_from_query!(u128);
 impl_int_from_query!(usize);
 
-// TODO: rails-like decoding
+pub trait ToQuery {
+    /// Returns the query values, before percent-encoding. Empty means the key is omitted.
+    fn to_query(&self) -> Vec<String>;
+}
+
+impl<T> ToQuery for Vec<T>
+where
+    T: ToQuery,
+{
+    fn to_query(&self) -> Vec<String> {
+        self.iter().flat_map(T::to_query).collect()
+    }
+}
+
+impl<T> ToQuery for Option<T>
+where
+    T: ToQuery,
+{
+    fn to_query(&self) -> Vec<String> {
+        if let Some(value) = self {
+            value.to_query()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl ToQuery for String {
+    fn to_query(&self) -> Vec<String> {
+        vec![self.clone()]
+    }
+}
+
+macro_rules! impl_int_to_query {
+    ($($int:ty)*) => {
+        $(
+            impl ToQuery for $int {
+                fn to_query(&self) -> Vec<String> {
+                    vec![self.to_string()]
+                }
+            }
+        )*
+    };
+}
+impl_int_to_query!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+/// Parses a header from all of its values, in order.
+pub trait FromHeader: Sized {
+    fn from_header(values: &[&HeaderValue]) -> Result<Self, HeaderError>;
+}
+
+fn require_one_header<'a>(values: &[&'a HeaderValue]) -> Result<&'a HeaderValue, HeaderError> {
+    match values {
+        [value] => Ok(value),
+        [] => Err(HeaderError::NoHeader),
+        _ => Err(HeaderError::MultipleHeader),
+    }
+}
+
+impl<T> FromHeader for Vec<T>
+where
+    T: FromHeader,
+{
+    fn from_header(values: &[&HeaderValue]) -> Result<Self, HeaderError> {
+        values
+            .iter()
+            .map(|x| T::from_header(slice::from_ref(x)))
+            .collect()
+    }
+}
+
+impl<T> FromHeader for Option<T>
+where
+    T: FromHeader,
+{
+    fn from_header(values: &[&HeaderValue]) -> Result<Self, HeaderError> {
+        if values.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(T::from_header(values)?))
+        }
+    }
+}
+
+impl FromHeader for HeaderValue {
+    fn from_header(values: &[&HeaderValue]) -> Result<Self, HeaderError> {
+        Ok(require_one_header(values)?.clone())
+    }
+}
+
+impl FromHeader for String {
+    fn from_header(values: &[&HeaderValue]) -> Result<Self, HeaderError> {
+        Ok(require_one_header(values)?.to_str()?.to_owned())
+    }
+}
+
+macro_rules! impl_int_from_header {
+    ($($T:ty)*) => {
+        $(
+            impl FromHeader for $T {
+                fn from_header(values: &[&HeaderValue]) -> Result<Self, HeaderError> {
+                    Ok(require_one_header(values)?.to_str()?.parse()?)
+                }
+            }
+        )*
+    };
+}
+impl_int_from_header!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+/// Collects cookies from all `Cookie` headers, keeping values with the same name in order.
+///
+/// Values are taken verbatim, except that surrounding double quotes are removed.
+/// Malformed pairs and non-UTF-8 headers are skipped.
+pub fn parse_cookies(headers: &HeaderMap) -> HashMap<String, Vec<String>> {
+    let mut cookies = HashMap::<_, Vec<_>>::new();
+    for value in headers.get_all(COOKIE) {
+        let value = if let Ok(value) = value.to_str() {
+            value
+        } else {
+            continue;
+        };
+        for pair in value.split(';') {
+            let pair = pair.trim();
+            let (name, value) = if let Some(idx) = pair.find('=') {
+                (pair[..idx].trim(), pair[idx + 1..].trim())
+            } else {
+                continue;
+            };
+            if name.is_empty() {
+                continue;
+            }
+            let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                &value[1..value.len() - 1]
+            } else {
+                value
+            };
+            cookies
+                .entry(name.to_owned())
+                .or_default()
+                .push(value.to_owned());
+        }
+    }
+    cookies
+}
+
+/// Parses a cookie from all values sent under its name, in order.
+pub trait FromCookie: Sized {
+    fn from_cookie(values: &[String]) -> Result<Self, CookieError>;
+}
+
+fn require_one_cookie(values: &[String]) -> Result<&str, CookieError> {
+    match values {
+        [value] => Ok(value),
+        [] => Err(CookieError::NoCookie),
+        _ => Err(CookieError::MultipleCookie),
+    }
+}
+
+impl<T> FromCookie for Vec<T>
+where
+    T: FromCookie,
+{
+    fn from_cookie(values: &[String]) -> Result<Self, CookieError> {
+        values
+            .iter()
+            .map(|x| T::from_cookie(slice::from_ref(x)))
+            .collect()
+    }
+}
+
+impl<T> FromCookie for Option<T>
+where
+    T: FromCookie,
+{
+    fn from_cookie(values: &[String]) -> Result<Self, CookieError> {
+        if values.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(T::from_cookie(values)?))
+        }
+    }
+}
+
+impl FromCookie for String {
+    fn from_cookie(values: &[String]) -> Result<Self, CookieError> {
+        Ok(require_one_cookie(values)?.to_owned())
+    }
+}
+
+macro_rules! impl_int_from_cookie {
+    ($($T:ty)*) => {
+        $(
+            impl FromCookie for $T {
+                fn from_cookie(values: &[String]) -> Result<Self, CookieError> {
+                    Ok(require_one_cookie(values)?.parse()?)
+                }
+            }
+        )*
+    };
+}
+impl_int_from_cookie!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+/// Builds the request URI back from a request struct.
+///
+/// `#[derive(Preroute)]` implements this when `#[nails(to_uri)]` is given.
+/// The path and query fields then need `ToPath`, `ToPathRest`, `ToQuery` or `ToQueryStruct`.
+pub trait ToUri {
+    fn to_uri(&self) -> Uri;
+}
+
+pub fn build_uri(path: String, query: &[(&str, String)]) -> Uri {
+    let mut uri = path;
+    for (i, (key, value)) in query.iter().enumerate() {
+        uri.push(if i == 0 { '?' } else { '&' });
+        uri.push_str(&encode_query_component(key));
+        uri.push('=');
+        uri.push_str(&encode_query_component(value));
+    }
+    uri.parse().expect("invalid URI")
+}
+
+pub fn encode_path_rest(components: &[String]) -> String {
+    components
+        .iter()
+        .map(|component| encode_path_component(component))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn encode_path_component(input: &str) -> String {
+    percent_encode(input, |c| b"!$&'()*+,;=:@".contains(&c))
+}
+
+fn encode_query_component(input: &str) -> String {
+    percent_encode(input, |c| b"!$'()*,;:@/?".contains(&c))
+}
+
+fn percent_encode(input: &str, allowed: impl Fn(u8) -> bool) -> String {
+    let mut output = String::with_capacity(input.len());
+    for &c in input.as_bytes() {
+        if c.is_ascii_alphanumeric() || b"-._~".contains(&c) || allowed(c) {
+            output.push(c as char);
+        } else {
+            output.push_str(&format!("%{:02X}", c));
+        }
+    }
+    output
+}
+
 // TODO: consider less-allocation way to decode query
 // TODO: handle illformed keys and values
 pub fn parse_query(query: &str) -> HashMap<String, Vec<String>> {
     let mut hash: HashMap<String, Vec<String>> = HashMap::new();
-    for pair in query.split("&") {
-        let (key, value) = if let Some(pair) = parse_query_pair(pair) {
-            pair
-        } else {
-            // TODO: handle errors
-            continue;
-        };
+    for (key, value) in parse_query_pairs(query) {
         match hash.entry(key) {
             Entry::Occupied(mut entry) => {
                 entry.get_mut().push(value);
@@ -183,6 +868,12 @@ pub fn parse_query(query: &str) -> HashMap<String, Vec<String>> {
     hash
 }
 
+/// Decodes the query pairs in order. See `crate::query::parse_nested_query` for the bracket syntax.
+pub(crate) fn parse_query_pairs(query: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    // TODO: handle errors
+    query.split("&").filter_map(parse_query_pair)
+}
+
 // TODO: optimize
 // TODO: better error handling
 fn parse_query_pair(pair: &str) -> Option<(String, String)> {
@@ -230,6 +921,8 @@ fn parse_percent_encoding(input: &str) -> Option<String> {
 mod tests {
     use super::*;
 
+    use nails_pattern::Constraint;
+
     macro_rules! hash {
         ($($e:expr),*) => {
             vec![$($e,)*].into_iter().collect::<std::collections::HashMap<_, _>>()
@@ -256,6 +949,105 @@ mod tests {
         assert!(!i32::matches(" 5"));
         assert!(!i8::matches("200"));
         assert!(!u32::matches("-1"));
+
+        assert_eq!(String::from_raw_path("%20%E3%81%82", false), Ok(S(" あ")));
+        assert_eq!(String::from_raw_path("a%2Fb", false), Err(()));
+        assert_eq!(String::from_raw_path("a%2fb", true), Ok(S("a/b")));
+        assert_eq!(String::from_raw_path("%", false), Err(()));
+        assert_eq!(i32::from_raw_path("%32", false), Ok(2));
+        assert_eq!(
+            RawPathSegment::from_raw_path("jos%C3%A9%2F", false),
+            Ok(RawPathSegment(S("jos%C3%A9%2F"))),
+        );
+        assert!(String::matches_raw("jos%C3%A9", false));
+        assert!(!String::matches_raw("a%2Fb", false));
+        assert!(RawPathSegment::matches_raw("a%2Fb", false));
+    }
+
+    #[test]
+    fn test_split_path_component() {
+        assert_eq!(
+            split_path_component("archive.tar.gz", &["", ".", ""]),
+            Some(vec!["archive.tar", "gz"]),
+        );
+        assert_eq!(split_path_component("README", &["", ".", ""]), None);
+        assert_eq!(split_path_component("v2", &["v", ""]), Some(vec!["2"]));
+        assert_eq!(split_path_component("v", &["v", ""]), Some(vec![""]));
+        assert_eq!(split_path_component("2", &["v", ""]), None);
+        assert_eq!(
+            split_path_component("{1}-{2}", &["{", "}-{", "}"]),
+            Some(vec!["1", "2"]),
+        );
+        assert_eq!(split_path_component("aba", &["ab", "ba"]), None);
+    }
+
+    #[test]
+    fn test_path_constraint() {
+        let constraint = PathConstraint::new("^(?:\\d{1,8})$", false);
+        assert!(constraint.matches("12345678"));
+        assert!(!constraint.matches("123456789"));
+        assert!(!constraint.matches("x1"));
+
+        let constraint = PathConstraint::from_parsed(Constraint::parse("!feed").unwrap());
+        assert!(!constraint.matches("feed"));
+        assert!(!constraint.matches_raw("fe%65d", false));
+        assert!(!constraint.matches_raw("a%2Fb", false));
+        assert!(constraint.matches_raw("a%2Fb", true));
+        assert!(constraint.matches("feeds"));
+        assert!(constraint.matches("how-to-train-your-dragon"));
+    }
+
+    #[test]
+    fn test_from_path_rest() {
+        assert_eq!(String::from_path_rest("a/b/"), Ok(S("a/b/")));
+        assert_eq!(String::from_path_rest(""), Ok(S("")));
+        assert_eq!(
+            Vec::<String>::from_path_rest("a/b/"),
+            Ok(vec![S("a"), S("b"), S("")])
+        );
+        assert_eq!(Vec::<String>::from_path_rest(""), Ok(vec![]));
+        assert_eq!(Vec::<u32>::from_path_rest("1/2"), Ok(vec![1, 2]));
+        assert_eq!(Vec::<u32>::from_path_rest("1/x"), Err(()));
+
+        assert!(String::matches_rest(""));
+        assert!(Vec::<u32>::matches_rest(""));
+        assert!(Vec::<u32>::matches_rest("1/2"));
+        assert!(!Vec::<u32>::matches_rest("1/2/"));
+
+        assert_eq!(
+            Vec::<String>::from_raw_path_rest("a%2Fb/c%20d", true),
+            Ok(vec![S("a/b"), S("c d")]),
+        );
+        assert_eq!(Vec::<String>::from_raw_path_rest("a%2Fb/c", false), Err(()));
+        assert_eq!(String::from_raw_path_rest("a/c%20d", false), Ok(S("a/c d")));
+    }
+
+    #[test]
+    fn test_from_path_rest_dot_segments() {
+        assert_eq!(String::from_path_rest("../../etc/passwd"), Err(()));
+        assert_eq!(String::from_path_rest("a/./b"), Err(()));
+        assert!(!String::matches_rest("a/.."));
+        assert_eq!(String::from_path_rest("a/.b/..."), Ok(S("a/.b/...")));
+        assert_eq!(
+            String::from_raw_path_rest("%2E%2E/%2e%2e/etc/passwd", false),
+            Err(())
+        );
+        assert!(!String::matches_raw_rest("a/%2E", false));
+        assert_eq!(String::from_raw_path_rest("a%2F..", true), Err(()));
+
+        assert_eq!(Vec::<String>::from_path_rest("a/../b"), Err(()));
+        assert!(!Vec::<String>::matches_rest("."));
+        assert_eq!(
+            Vec::<String>::from_raw_path_rest("%2E%2E/%2E%2E/etc/passwd", false),
+            Err(())
+        );
+        assert!(!Vec::<String>::matches_raw_rest("a/.%2E", false));
+
+        assert_eq!(
+            RawPathRest::from_raw_path_rest("%2E%2E/etc/passwd", false),
+            Ok(RawPathRest(S("%2E%2E/etc/passwd")))
+        );
+        assert!(RawPathRest::matches_raw_rest("../etc", false));
     }
 
     #[test]
@@ -298,6 +1090,565 @@ mod tests {
         assert_eq!(parse_query("foo&foo=foo=foo&f%oo=1&1=%E3"), hash![]);
     }
 
+    #[test]
+    fn test_to_query() {
+        assert_eq!(S("foo").to_query(), vec![S("foo")]);
+        assert_eq!(Option::<String>::None.to_query(), Vec::<String>::new());
+        assert_eq!(Some(42).to_query(), vec![S("42")]);
+        assert_eq!(vec![1, 2].to_query(), vec![S("1"), S("2")]);
+    }
+
+    #[test]
+    fn test_build_uri() {
+        assert_eq!(build_uri(S("/api/posts"), &[]), "/api/posts");
+        assert_eq!(
+            build_uri(
+                S("/api/posts"),
+                &[("tag", S("a&b c")), ("tag", S("あ")), ("limit", S("20"))]
+            ),
+            "/api/posts?tag=a%26b%20c&tag=%E3%81%82&limit=20",
+        );
+    }
+
+    #[test]
+    fn test_encode_path_component() {
+        assert_eq!(encode_path_component("foo-bar_1.2~"), "foo-bar_1.2~");
+        assert_eq!(encode_path_component("a/b c?d#"), "a%2Fb%20c%3Fd%23");
+        assert_eq!(
+            encode_path_component("user@example.com"),
+            "user@example.com"
+        );
+        assert_eq!(encode_path_component("josé"), "jos%C3%A9");
+        assert_eq!(parse_percent_encoding("jos%C3%A9"), Some(S("josé")));
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/v{version}/files/{name}.{ext}", to_uri)]
+    struct GetFileRequest {
+        version: u32,
+        name: String,
+        ext: String,
+    }
+
+    #[test]
+    fn test_partial_path_component() {
+        assert!(GetFileRequest::match_path(
+            &Method::GET,
+            "/api/v2/files/a.tar.gz"
+        ));
+        assert!(!GetFileRequest::match_path(
+            &Method::GET,
+            "/api/vx/files/a.tar.gz"
+        ));
+        assert!(!GetFileRequest::match_path(
+            &Method::GET,
+            "/api/v2/files/README"
+        ));
+        assert_eq!(GetFileRequest::path_prefix_hint(), "/api/v");
+
+        let req = Request::builder()
+            .uri("/api/v2/files/a.tar.gz")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(GetFileRequest::from_request(req)).unwrap();
+        assert_eq!(req.version, 2);
+        assert_eq!(req.name, "a.tar");
+        assert_eq!(req.ext, "gz");
+        assert_eq!(req.to_uri(), "/api/v2/files/a.tar.gz");
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/articles/{slug:!feed}/comments/{id:\\d{1,8}}")]
+    struct GetCommentRequest {
+        #[allow(dead_code)]
+        slug: String,
+        #[allow(dead_code)]
+        id: String,
+    }
+
+    #[test]
+    fn test_constrained_path() {
+        assert!(GetCommentRequest::match_path(
+            &Method::GET,
+            "/api/articles/dragon/comments/42"
+        ));
+        assert!(!GetCommentRequest::match_path(
+            &Method::GET,
+            "/api/articles/feed/comments/42"
+        ));
+        assert!(!GetCommentRequest::match_path(
+            &Method::GET,
+            "/api/articles/dragon/comments/123456789"
+        ));
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/articles/{slug:!feed}/{title:!index}")]
+    struct GetArticlePageRequest {
+        #[allow(dead_code)]
+        slug: RawPathSegment,
+        #[allow(dead_code)]
+        #[nails(path, allow_encoded_slash)]
+        title: RawPathSegment,
+    }
+
+    #[test]
+    fn test_constrained_path_encoded_slash() {
+        assert!(GetArticlePageRequest::match_path(
+            &Method::GET,
+            "/api/articles/dragon/a%2Fb"
+        ));
+        assert!(!GetArticlePageRequest::match_path(
+            &Method::GET,
+            "/api/articles/a%2Fb/index"
+        ));
+        assert!(!GetArticlePageRequest::match_path(
+            &Method::GET,
+            "/api/articles/a%2Fb/c"
+        ));
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/{name}/{raw}/{slashed}", to_uri)]
+    struct DecodeRequest {
+        name: String,
+        raw: RawPathSegment,
+        #[nails(path, allow_encoded_slash)]
+        slashed: String,
+    }
+
+    #[test]
+    fn test_path_decoding() {
+        assert!(DecodeRequest::match_path(
+            &Method::GET,
+            "/api/users/jos%C3%A9/a%2Fb/c%2Fd"
+        ));
+        assert!(!DecodeRequest::match_path(
+            &Method::GET,
+            "/api/users/a%2Fb/a%2Fb/c%2Fd"
+        ));
+        assert!(!DecodeRequest::match_path(
+            &Method::GET,
+            "/api/users/%ff/a/b"
+        ));
+
+        let req = Request::builder()
+            .uri("/api/users/jos%C3%A9/a%2Fb/c%2Fd")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(DecodeRequest::from_request(req)).unwrap();
+        assert_eq!(req.name, "josé");
+        assert_eq!(req.raw, RawPathSegment(S("a%2Fb")));
+        assert_eq!(req.slashed, "c/d");
+        assert_eq!(req.to_uri(), "/api/users/jos%C3%A9/a%2Fb/c%2Fd");
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/static/{*path}", to_uri)]
+    struct StaticRequest {
+        path: Vec<String>,
+    }
+
+    #[test]
+    fn test_path_rest() {
+        assert!(StaticRequest::match_path(&Method::GET, "/static/"));
+        assert!(StaticRequest::match_path(&Method::GET, "/static/js/app.js"));
+        assert!(!StaticRequest::match_path(&Method::GET, "/static"));
+        assert!(!StaticRequest::match_path(&Method::GET, "/statics/app.js"));
+        assert!(!StaticRequest::match_path(
+            &Method::GET,
+            "/static/../etc/passwd"
+        ));
+        assert!(!StaticRequest::match_path(
+            &Method::GET,
+            "/static/%2E%2E/%2E%2E/etc/passwd"
+        ));
+
+        let req = Request::builder()
+            .uri("/static/js/app.js")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(StaticRequest::from_request(req)).unwrap();
+        assert_eq!(req.path, vec![S("js"), S("app.js")]);
+        assert_eq!(req.to_uri(), "/static/js/app.js");
+        assert_eq!(StaticRequest { path: vec![] }.to_uri(), "/static/");
+    }
+
+    #[test]
+    fn test_request_host() {
+        let host = |uri: &str, header: Option<&str>| {
+            let mut req = Request::builder();
+            req.uri(uri);
+            if let Some(header) = header {
+                req.header("Host", header);
+            }
+            request_host(&req.body(()).unwrap()).map(str::to_owned)
+        };
+        assert_eq!(host("/", Some("example.com")), Some(S("example.com")));
+        assert_eq!(host("/", Some("example.com:8080")), Some(S("example.com")));
+        assert_eq!(host("/", Some("example.com.")), Some(S("example.com")));
+        assert_eq!(host("/", Some("[::1]:8080")), Some(S("[::1]")));
+        assert_eq!(
+            host("http://api.example.com/", Some("example.com")),
+            Some(S("api.example.com"))
+        );
+        assert_eq!(host("/", None), None);
+    }
+
+    #[test]
+    fn test_match_host() {
+        let pattern = [None, Some("example"), Some("com")];
+        assert_eq!(
+            match_host("acme.Example.COM", &pattern),
+            Some(vec!["acme", "Example", "COM"])
+        );
+        assert_eq!(match_host("example.com", &pattern), None);
+        assert_eq!(match_host("a.b.example.com", &pattern), None);
+        assert_eq!(match_host(".example.com", &pattern), None);
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/{id}", host = "{tenant}.example.com")]
+    struct GetTenantUserRequest {
+        tenant: String,
+        id: u64,
+    }
+
+    #[test]
+    fn test_host() {
+        let head = |host| RequestHead::new(&Method::GET, "/api/users/42").with_host(host);
+        assert!(GetTenantUserRequest::match_head(&head(Some(
+            "acme.example.com"
+        ))));
+        assert!(!GetTenantUserRequest::match_head(&head(Some(
+            "example.com"
+        ))));
+        assert!(!GetTenantUserRequest::match_head(&head(None)));
+        assert_eq!(
+            GetTenantUserRequest::route_patterns()[0].host,
+            Some("{tenant}.example.com")
+        );
+
+        let req = Request::builder()
+            .uri("/api/users/42")
+            .header("Host", "acme.example.com:3000")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(GetTenantUserRequest::from_request(req)).unwrap();
+        assert_eq!(req.tenant, "acme");
+        assert_eq!(req.id, 42);
+    }
+
+    #[test]
+    fn test_from_header() {
+        let value = |s: &'static str| HeaderValue::from_static(s);
+        let (a, b) = (value("1"), value("2"));
+        assert_eq!(String::from_header(&[&a]).unwrap(), "1");
+        assert!(String::from_header(&[]).is_err());
+        assert!(String::from_header(&[&a, &b]).is_err());
+        assert_eq!(Option::<u32>::from_header(&[]).unwrap(), None);
+        assert_eq!(Option::<u32>::from_header(&[&b]).unwrap(), Some(2));
+        assert_eq!(Vec::<u32>::from_header(&[&a, &b]).unwrap(), vec![1, 2]);
+        assert!(u32::from_header(&[&value("x")]).is_err());
+        assert_eq!(HeaderValue::from_header(&[&a]).unwrap(), a);
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/articles")]
+    struct ListArticlesRequest {
+        #[nails(header = "If-None-Match")]
+        etag: Option<String>,
+        #[nails(header)]
+        x_request_id: HeaderValue,
+        #[nails(header = "X-Shard")]
+        shards: Vec<u32>,
+    }
+
+    #[test]
+    fn test_header() {
+        let req = Request::builder()
+            .uri("/api/articles")
+            .header("if-none-match", "\"abc\"")
+            .header("X-Request-Id", "req-1")
+            .header("X-Shard", "1")
+            .header("X-Shard", "3")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(ListArticlesRequest::from_request(req)).unwrap();
+        assert_eq!(req.etag.as_ref().map(|s| &s[..]), Some("\"abc\""));
+        assert_eq!(req.x_request_id, "req-1");
+        assert_eq!(req.shards, vec![1, 3]);
+
+        let req = Request::builder()
+            .uri("/api/articles")
+            .body(Body::empty())
+            .unwrap();
+        let err = futures::executor::block_on(ListArticlesRequest::from_request(req)).unwrap_err();
+        match err {
+            NailsError::HeaderError(HeaderError::NoHeader) => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_parse_cookies() {
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("a=1; b=\"x y\";c"));
+        headers.append(COOKIE, HeaderValue::from_static(" a = 2 ;d="));
+        assert_eq!(
+            parse_cookies(&headers),
+            hash![
+                (S("a"), vec![S("1"), S("2")]),
+                (S("b"), vec![S("x y")]),
+                (S("d"), vec![S("")]),
+            ],
+        );
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/user")]
+    struct GetCurrentUserRequest {
+        #[nails(cookie = "SID")]
+        session_id: String,
+        #[nails(cookie)]
+        page_size: Option<u32>,
+    }
+
+    #[test]
+    fn test_cookie() {
+        let req = Request::builder()
+            .uri("/api/user")
+            .header("Cookie", "theme=dark; SID=a3fWa; page_size=20")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(GetCurrentUserRequest::from_request(req)).unwrap();
+        assert_eq!(req.session_id, "a3fWa");
+        assert_eq!(req.page_size, Some(20));
+
+        let req = Request::builder()
+            .uri("/api/user")
+            .header("Cookie", "SID=a3fWa; SID=b4gXb")
+            .body(Body::empty())
+            .unwrap();
+        let err =
+            futures::executor::block_on(GetCurrentUserRequest::from_request(req)).unwrap_err();
+        match err {
+            NailsError::CookieError(CookieError::MultipleCookie) => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[derive(Debug, Preroute)]
+    enum FeedRequest {
+        #[nails(path = "/api/feed/{id}")]
+        Article {
+            #[allow(dead_code)]
+            id: u64,
+        },
+        #[nails(path = "/api/feed/{name}", method = ["GET", "HEAD"])]
+        Named {
+            #[allow(dead_code)]
+            name: String,
+        },
+    }
+
+    #[test]
+    fn test_enum_explicit_head() {
+        let head = |path| FeedRequest::explicit_head(&RequestHead::new(&Method::HEAD, path));
+        assert!(!head("/api/feed/42"));
+        assert!(head("/api/feed/recent"));
+    }
+
+    #[derive(Debug, PartialEq, Preroute)]
+    #[nails(to_uri)]
+    enum FavoriteRequest {
+        #[nails(path = "/api/articles/{slug}/favorite", method = "POST")]
+        Favorite { slug: String },
+        #[nails(path = "/api/articles/{slug}/favorite", method = "DELETE")]
+        Unfavorite { slug: String },
+    }
+
+    #[test]
+    fn test_enum() {
+        assert_eq!(FavoriteRequest::path_prefix_hint(), "/api/articles/");
+        assert_eq!(FavoriteRequest::route_patterns().len(), 2);
+        assert!(FavoriteRequest::match_path(
+            &Method::DELETE,
+            "/api/articles/foo/favorite"
+        ));
+        assert!(!FavoriteRequest::match_path(
+            &Method::GET,
+            "/api/articles/foo/favorite"
+        ));
+        assert_eq!(
+            FavoriteRequest::allowed_methods("/api/articles/foo/favorite"),
+            vec![Method::POST, Method::DELETE],
+        );
+
+        let req = Request::builder()
+            .method(Method::DELETE)
+            .uri("/api/articles/foo/favorite")
+            .body(Body::empty())
+            .unwrap();
+        let req = futures::executor::block_on(FavoriteRequest::from_request(req)).unwrap();
+        assert_eq!(req, FavoriteRequest::Unfavorite { slug: S("foo") });
+        assert_eq!(req.to_uri(), "/api/articles/foo/favorite");
+    }
+
+    #[derive(Debug, PartialEq, Preroute)]
+    enum HomeRequest {
+        #[nails(path = "/", host = "{tenant}.example.com")]
+        Tenant { tenant: String },
+        #[nails(path = "/")]
+        Default,
+    }
+
+    #[test]
+    fn test_enum_host() {
+        let head = |host| RequestHead::new(&Method::GET, "/").with_host(host);
+        assert!(HomeRequest::match_head(&head(Some("acme.example.com"))));
+        assert!(HomeRequest::match_head(&head(None)));
+
+        let request = |host: &str| {
+            let req = Request::builder()
+                .uri("/")
+                .header("Host", host)
+                .body(Body::empty())
+                .unwrap();
+            futures::executor::block_on(HomeRequest::from_request(req)).unwrap()
+        };
+        assert_eq!(
+            request("acme.example.com"),
+            HomeRequest::Tenant { tenant: S("acme") }
+        );
+        assert_eq!(request("example.com"), HomeRequest::Default);
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/posts")]
+    struct ListPostsRequest {
+        #[nails(query, default = "20")]
+        limit: u32,
+        #[nails(default)]
+        offset: u32,
+        #[nails(default = "vec![String::from(\"rust\")]")]
+        tag: Vec<String>,
+    }
+
+    #[test]
+    fn test_query_default() {
+        let request = |uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            futures::executor::block_on(ListPostsRequest::from_request(req))
+        };
+        let req = request("/api/posts").unwrap();
+        assert_eq!((req.limit, req.offset), (20, 0));
+        assert_eq!(req.tag, vec![S("rust")]);
+        let req = request("/api/posts?limit=5&offset=10&tag=a&tag=b").unwrap();
+        assert_eq!((req.limit, req.offset), (5, 10));
+        assert_eq!(req.tag, vec![S("a"), S("b")]);
+        assert!(request("/api/posts?limit=x").is_err());
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct PostFilter {
+        author: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/posts/search", to_uri)]
+    struct SearchPostsRequest {
+        q: Option<String>,
+        #[nails(query_struct)]
+        filter: crate::query::Query<PostFilter>,
+    }
+
+    #[test]
+    fn test_query_struct() {
+        let request = |uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            futures::executor::block_on(SearchPostsRequest::from_request(req))
+        };
+        let req = request("/api/posts/search?q=nails").unwrap();
+        assert_eq!(req.q, Some(S("nails")));
+        assert_eq!(
+            req.filter.0,
+            PostFilter {
+                author: None,
+                tags: vec![],
+            },
+        );
+        let req =
+            request("/api/posts/search?filter[author]=jake&filter[tags][]=a&filter[tags][]=b")
+                .unwrap();
+        assert_eq!(
+            req.filter.0,
+            PostFilter {
+                author: Some(S("jake")),
+                tags: vec![S("a"), S("b")],
+            },
+        );
+        assert_eq!(
+            req.to_uri(),
+            "/api/posts/search?filter%5Bauthor%5D=jake&filter%5Btags%5D%5B%5D=a&filter%5Btags%5D%5B%5D=b",
+        );
+        assert!(request("/api/posts/search?filter[author][]=jake&filter[author][]=josh").is_err());
+    }
+
+    // No `Serialize`: `query_struct` only needs it with `#[nails(to_uri)]`.
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct PageParams {
+        size: u32,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/posts/recent")]
+    struct RecentPostsRequest {
+        #[nails(query_struct)]
+        page: Option<crate::query::Query<PageParams>>,
+    }
+
+    #[test]
+    fn test_query_struct_without_to_uri() {
+        let request = |uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            futures::executor::block_on(RecentPostsRequest::from_request(req))
+        };
+        assert!(request("/api/posts/recent").unwrap().page.is_none());
+        let req = request("/api/posts/recent?page[size]=10").unwrap();
+        assert_eq!(req.page.unwrap().0, PageParams { size: 10 });
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/{name}/posts/{id}", to_uri)]
+    struct GetPostRequest {
+        name: String,
+        id: u64,
+        tag: Vec<String>,
+        #[nails(query = "n")]
+        limit: Option<u32>,
+    }
+
+    #[test]
+    fn test_to_uri() {
+        let req = GetPostRequest {
+            name: S("a b/c"),
+            id: 42,
+            tag: vec![S("x"), S("y")],
+            limit: None,
+        };
+        assert_eq!(req.to_uri(), "/api/users/a%20b%2Fc/posts/42?tag=x&tag=y");
+        let req = GetPostRequest {
+            name: S("jake"),
+            id: 1,
+            tag: vec![],
+            limit: Some(10),
+        };
+        assert_eq!(req.to_uri(), "/api/users/jake/posts/1?n=10");
+    }
+
     #[test]
     fn test_parse_percent_encoding() {
         assert_eq!(parse_percent_encoding("foo"), Some(S("foo")));
@@ -1,20 +1,34 @@
 use futures::prelude::*;
 
+use std::cmp::Ordering;
 use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 use async_trait::async_trait;
-use contextful::Context;
-use hyper::{Body, Method, Request, Response};
+use contextful::{AsContext, Context};
+use hyper::http::uri::PathAndQuery;
+use hyper::{Body, Method, Request, Response, Uri};
 
-use crate::error::NailsError;
-use crate::request::Preroute;
+use crate::error::{NailsError, RouteConflict, RouteConflictError};
+use crate::request::{Preroute, RequestHead};
+
+use self::pattern::{HostShape, PathShape};
+use self::table::{common_prefix_len, RadixTree};
+
+mod pattern;
+mod table;
+
+pub(crate) type SharedRoute<Ctx> = Arc<dyn Routable<Ctx = Ctx> + Send + Sync + 'static>;
 
 pub struct Router<Ctx>
 where
     Ctx: Context + Send + Sync + 'static,
 {
-    routes: Vec<Box<dyn Routable<Ctx = Ctx> + Send + Sync + 'static>>,
+    routes: Vec<SharedRoute<Ctx>>,
+    table: RadixTree<usize>,
+    priority: Vec<usize>,
+    prefix_hint: String,
     _marker: PhantomData<fn(Ctx)>,
 }
 
@@ -25,6 +39,9 @@ where
     pub fn new() -> Self {
         Self {
             routes: Vec::new(),
+            table: RadixTree::new(),
+            priority: Vec::new(),
+            prefix_hint: String::new(),
             _marker: PhantomData,
         }
     }
@@ -33,7 +50,21 @@ where
     where
         R: Routable<Ctx = Ctx> + Send + Sync + 'static,
     {
-        self.routes.push(Box::new(route));
+        let hint = route.path_prefix_hint();
+        if self.routes.is_empty() {
+            self.prefix_hint = hint.to_owned();
+        } else {
+            let len = common_prefix_len(self.prefix_hint.as_bytes(), hint.as_bytes());
+            // Don't cut a multibyte character in half.
+            let len = (0..=len)
+                .rev()
+                .find(|&len| self.prefix_hint.is_char_boundary(len))
+                .unwrap();
+            self.prefix_hint.truncate(len);
+        }
+        self.table.insert(hint, self.routes.len());
+        self.priority.push(self.routes.len());
+        self.routes.push(Arc::new(route));
     }
 
     pub fn add_function_route<F, Fut, Req>(&mut self, route: F)
@@ -44,6 +75,291 @@ where
     {
         self.add_route(FunctionRoute::new(route))
     }
+
+    /// Adds a function route which only needs a part of the context.
+    ///
+    /// The context is narrowed through [`AsContext`](contextful::AsContext) for each request.
+    pub fn add_subcontext_function_route<SubCtx, F, Fut, Req>(&mut self, route: F)
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+        F: Fn(SubCtx, Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<Body>, NailsError>> + Send + 'static,
+        Req: Preroute + Send + 'static,
+    {
+        self.add_route(AsContextRoute::new(FunctionRoute::new(route)))
+    }
+
+    /// Adds the routes of `router` under `prefix`.
+    ///
+    /// A route for `/users` mounted under `/api` serves `/api/users` and sees
+    /// `/users` as the request path. The router may use a narrower context,
+    /// which is derived from ours through [`AsContext`](contextful::AsContext).
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `prefix` doesn't start with a slash.
+    pub fn mount<SubCtx>(&mut self, prefix: &str, router: Router<SubCtx>)
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        self.mount_routes(prefix, router.sorted_routes());
+    }
+
+    /// Adds the routes of `router`, served only for the hosts matching `host`.
+    ///
+    /// `host` is a pattern like `admin.example.com` or `{tenant}.example.com`,
+    /// where a `{...}` label matches any label. Hosts are compared case-insensitively.
+    /// The scheme and port play no part in the match.
+    pub fn mount_host<SubCtx>(&mut self, host: &str, router: Router<SubCtx>)
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        self.mount_host_routes(host, router.sorted_routes());
+    }
+
+    pub(crate) fn mount_host_routes<SubCtx>(&mut self, host: &str, routes: Vec<SharedRoute<SubCtx>>)
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        for route in routes {
+            self.add_route(HostRoute::new(host, Arc::new(AsContextRoute::new(route))));
+        }
+    }
+
+    pub(crate) fn mount_routes<SubCtx>(&mut self, prefix: &str, routes: Vec<SharedRoute<SubCtx>>)
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        assert!(
+            prefix.starts_with('/'),
+            "mount prefix must start with slash: {:?}",
+            prefix
+        );
+        let prefix = prefix.trim_end_matches('/');
+        for route in routes {
+            self.add_route(MountedRoute::new(
+                prefix,
+                Arc::new(AsContextRoute::new(route)),
+            ));
+        }
+    }
+
+    /// Returns the routes in the order they are tried.
+    pub(crate) fn sorted_routes(&self) -> Vec<SharedRoute<Ctx>> {
+        let mut order = (0..self.routes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&idx| self.priority[idx]);
+        order
+            .into_iter()
+            .map(|idx| self.routes[idx].clone())
+            .collect()
+    }
+
+    /// Finds the route responsible for the request.
+    ///
+    /// Only the routes whose `path_prefix_hint` is a prefix of the path are examined.
+    /// When several of them match, the one preferred by the last
+    /// [`resolve_conflicts`](Router::resolve_conflicts) wins.
+    pub fn find_route(
+        &self,
+        head: &RequestHead<'_>,
+    ) -> Option<&(dyn Routable<Ctx = Ctx> + Send + Sync + 'static)> {
+        let mut candidates = Vec::new();
+        self.table
+            .for_each_prefix(head.path(), |&idx| candidates.push(idx));
+        candidates.sort_by_key(|&idx| self.priority[idx]);
+        candidates
+            .into_iter()
+            .map(|idx| &*self.routes[idx])
+            .find(|route| route.match_request(head))
+    }
+
+    /// Collects the methods accepted for the request's path (and host) by any route.
+    pub fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        let mut candidates = Vec::new();
+        self.table
+            .for_each_prefix(head.path(), |&idx| candidates.push(idx));
+        candidates.sort_by_key(|&idx| self.priority[idx]);
+        let mut methods = Vec::new();
+        for idx in candidates {
+            for method in self.routes[idx].allowed_methods(head) {
+                if !methods.contains(&method) {
+                    methods.push(method);
+                }
+            }
+        }
+        methods
+    }
+
+    /// Looks for overlapping routes and decides which one wins according to `policy`.
+    ///
+    /// Overlaps the policy cannot settle are returned as an error. Overlaps between
+    /// the patterns of a single route, like a nested router, can't be reordered here,
+    /// so they count as settled only if the route already tries them in the right order.
+    /// Routes that don't describe their patterns are tried last, in registration order.
+    pub fn resolve_conflicts(&mut self, policy: ConflictPolicy) -> Result<(), RouteConflictError> {
+        let infos = self
+            .routes
+            .iter()
+            .map(|route| {
+                route
+                    .routes()
+                    .into_iter()
+                    .map(|info| {
+                        let shape = PathShape::parse(&info.path);
+                        let host = info.host.as_deref().map(HostShape::parse);
+                        (info, shape, host)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut order = (0..self.routes.len()).collect::<Vec<_>>();
+        if policy == ConflictPolicy::StaticFirst {
+            order.sort_by(
+                |&lhs, &rhs| match (infos[lhs].first(), infos[rhs].first()) {
+                    (
+                        Some((lhs_info, lhs_shape, lhs_host)),
+                        Some((rhs_info, rhs_shape, rhs_host)),
+                    ) => lhs_shape
+                        .cmp_specificity(rhs_shape)
+                        .then(cmp_hosts(lhs_host, rhs_host))
+                        .then(lhs_info.methods.len().cmp(&rhs_info.methods.len())),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                },
+            );
+        }
+        for (rank, &idx) in order.iter().enumerate() {
+            self.priority[idx] = rank;
+        }
+
+        // A route lists its own patterns in the order it tries them.
+        let patterns = infos
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, infos)| infos.iter().map(move |info| (idx, info)))
+            .collect::<Vec<_>>();
+        let mut conflicts = Vec::new();
+        for (pos, &(i, (lhs_info, lhs_shape, lhs_host))) in patterns.iter().enumerate() {
+            for &(j, (rhs_info, rhs_shape, rhs_host)) in &patterns[pos + 1..] {
+                let lhs_tried_first = i == j || self.priority[i] < self.priority[j];
+                let overlaps = lhs_shape.overlaps(rhs_shape)
+                    && hosts_overlap(lhs_host, rhs_host)
+                    && lhs_info
+                        .methods
+                        .iter()
+                        .any(|method| rhs_info.methods.contains(method));
+                if !overlaps {
+                    continue;
+                }
+                let resolved = match policy {
+                    ConflictPolicy::RegistrationOrder => true,
+                    ConflictPolicy::Error => false,
+                    ConflictPolicy::StaticFirst => {
+                        match lhs_shape
+                            .cmp_specificity(rhs_shape)
+                            .then(cmp_hosts(lhs_host, rhs_host))
+                            .then_with(|| cmp_method_sets(&lhs_info.methods, &rhs_info.methods))
+                        {
+                            Ordering::Less => lhs_tried_first,
+                            Ordering::Greater => !lhs_tried_first,
+                            Ordering::Equal => false,
+                        }
+                    }
+                };
+                if !resolved {
+                    conflicts.push(RouteConflict {
+                        first: lhs_info.clone(),
+                        second: rhs_info.clone(),
+                    });
+                }
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(RouteConflictError { conflicts })
+        }
+    }
+}
+
+// A route restricted to some hosts is more specific than one serving any host.
+fn cmp_hosts(lhs: &Option<HostShape>, rhs: &Option<HostShape>) -> Ordering {
+    match (lhs, rhs) {
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+fn hosts_overlap(lhs: &Option<HostShape>, rhs: &Option<HostShape>) -> bool {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => lhs.overlaps(rhs),
+        _ => true,
+    }
+}
+
+// A route accepting strictly fewer methods is more specific.
+fn cmp_method_sets(lhs: &[Method], rhs: &[Method]) -> Ordering {
+    let lhs_in_rhs = lhs.iter().all(|method| rhs.contains(method));
+    let rhs_in_lhs = rhs.iter().all(|method| lhs.contains(method));
+    match (lhs_in_rhs, rhs_in_lhs) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// The route with a static segment at the leftmost differing position wins.
+    /// Among identical patterns, a route bound to a host wins over one that isn't,
+    /// and then the route accepting fewer methods wins.
+    StaticFirst,
+    /// The route registered first wins.
+    ///
+    /// This is the default. Patterns can overlap while their field types keep
+    /// them apart (`/users/me` and `/users/{id}` with `id: u64`), so the
+    /// routes are simply tried in order, as the first matching route responds.
+    #[default]
+    RegistrationOrder,
+    /// Any overlap is an error.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    pub methods: Vec<Method>,
+    /// The host pattern, for routes served only on some hosts.
+    pub host: Option<String>,
+    pub path: String,
+    pub request_type: &'static str,
+    /// The handler's type name, for routes backed by a function.
+    pub handler: Option<&'static str>,
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, method) in self.methods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", method)?;
+        }
+        write!(
+            f,
+            " {}{} ({})",
+            self.host.as_deref().unwrap_or(""),
+            self.path,
+            self.request_type
+        )
+    }
 }
 
 impl<Ctx> fmt::Debug for Router<Ctx>
@@ -51,7 +367,9 @@ where
     Ctx: Context + Send + Sync + 'static,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("Router").finish()
+        f.debug_struct("Router")
+            .field("routes", &Routable::routes(self))
+            .finish()
     }
 }
 
@@ -62,29 +380,32 @@ where
 {
     type Ctx = Ctx;
 
-    fn match_path(&self, method: &Method, path: &str) -> bool {
-        self.routes
-            .iter()
-            .any(|route| route.match_path(method, path))
+    fn path_prefix_hint(&self) -> &str {
+        &self.prefix_hint
+    }
+    fn routes(&self) -> Vec<RouteInfo> {
+        self.sorted_routes()
+            .into_iter()
+            .flat_map(|route| route.routes())
+            .collect()
+    }
+    fn match_request(&self, head: &RequestHead<'_>) -> bool {
+        self.find_route(head).is_some()
+    }
+    fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        Router::allowed_methods(self, head)
+    }
+    fn explicit_head(&self, head: &RequestHead<'_>) -> bool {
+        self.find_route(head)
+            .map(|route| route.explicit_head(head))
+            .unwrap_or(false)
     }
     async fn respond(
         &self,
         ctx: &Self::Ctx,
         req: Request<Body>,
     ) -> Result<Response<Body>, NailsError> {
-        let method = req.method();
-        let path = req.uri().path();
-        let mut matched_route = None;
-        for route in &self.routes {
-            if route.match_path(method, path) {
-                if matched_route.is_some() {
-                    // TODO: make this Err
-                    panic!("multiple matching routes");
-                }
-                matched_route = Some(route);
-            }
-        }
-        matched_route
+        self.find_route(&RequestHead::from_request(&req))
             .expect("no route matched")
             .respond(&ctx, req)
             .await
@@ -95,10 +416,28 @@ where
 pub trait Routable {
     type Ctx: Context + Send + Sync + 'static;
 
+    /// A string every matching path starts with. The router uses it to skip
+    /// routes that cannot match, so it must never be longer than that.
     fn path_prefix_hint(&self) -> &str {
         ""
     }
-    fn match_path(&self, method: &Method, path: &str) -> bool;
+    /// Describes the patterns this route serves, for analysis and introspection.
+    fn routes(&self) -> Vec<RouteInfo> {
+        Vec::new()
+    }
+    fn match_request(&self, head: &RequestHead<'_>) -> bool;
+    /// Returns the methods accepted for the request's path (and host),
+    /// or an empty list if they don't match.
+    fn allowed_methods(&self, _head: &RequestHead<'_>) -> Vec<Method> {
+        Vec::new()
+    }
+    /// Returns true if the route responding to the HEAD request answers it by itself,
+    /// rather than as a GET whose body is dropped.
+    ///
+    /// Routers and mounts look through to the route they delegate to.
+    fn explicit_head(&self, _head: &RequestHead<'_>) -> bool {
+        false
+    }
     // TODO: Result
     async fn respond(
         &self,
@@ -107,6 +446,293 @@ pub trait Routable {
     ) -> Result<Response<Body>, NailsError>;
 }
 
+#[async_trait]
+impl<R> Routable for Arc<R>
+where
+    R: Routable + Send + Sync + ?Sized,
+{
+    type Ctx = R::Ctx;
+
+    fn path_prefix_hint(&self) -> &str {
+        (**self).path_prefix_hint()
+    }
+    fn routes(&self) -> Vec<RouteInfo> {
+        (**self).routes()
+    }
+    fn match_request(&self, head: &RequestHead<'_>) -> bool {
+        (**self).match_request(head)
+    }
+    fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        (**self).allowed_methods(head)
+    }
+    fn explicit_head(&self, head: &RequestHead<'_>) -> bool {
+        (**self).explicit_head(head)
+    }
+    async fn respond(
+        &self,
+        ctx: &Self::Ctx,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, NailsError> {
+        (**self).respond(ctx, req).await
+    }
+}
+
+/// Adapts a route written for a narrower context.
+pub struct AsContextRoute<Ctx, R> {
+    inner: R,
+    _marker: PhantomData<fn(Ctx)>,
+}
+
+impl<Ctx, R> AsContextRoute<Ctx, R>
+where
+    Ctx: AsContext<R::Ctx> + Send + Sync + 'static,
+    R: Routable + Send + Sync,
+{
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Ctx, R> fmt::Debug for AsContextRoute<Ctx, R>
+where
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsContextRoute")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<Ctx, R> Routable for AsContextRoute<Ctx, R>
+where
+    Ctx: AsContext<R::Ctx> + Send + Sync + 'static,
+    R: Routable + Send + Sync,
+{
+    type Ctx = Ctx;
+
+    fn path_prefix_hint(&self) -> &str {
+        self.inner.path_prefix_hint()
+    }
+    fn routes(&self) -> Vec<RouteInfo> {
+        self.inner.routes()
+    }
+    fn match_request(&self, head: &RequestHead<'_>) -> bool {
+        self.inner.match_request(head)
+    }
+    fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        self.inner.allowed_methods(head)
+    }
+    fn explicit_head(&self, head: &RequestHead<'_>) -> bool {
+        self.inner.explicit_head(head)
+    }
+    async fn respond(
+        &self,
+        ctx: &Self::Ctx,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, NailsError> {
+        let ctx = ctx.as_context();
+        self.inner.respond(&ctx, req).await
+    }
+}
+
+pub struct MountedRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    prefix: String,
+    prefix_hint: String,
+    inner: SharedRoute<Ctx>,
+}
+
+impl<Ctx> MountedRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    fn new(prefix: &str, inner: SharedRoute<Ctx>) -> Self {
+        let prefix_hint = format!("{}{}", prefix, inner.path_prefix_hint());
+        Self {
+            prefix: prefix.to_owned(),
+            prefix_hint,
+            inner,
+        }
+    }
+
+    fn strip_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        path.strip_prefix(self.prefix.as_str())
+            .filter(|rest| rest.starts_with('/'))
+    }
+}
+
+impl<Ctx> fmt::Debug for MountedRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MountedRoute")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<Ctx> Routable for MountedRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    type Ctx = Ctx;
+
+    fn path_prefix_hint(&self) -> &str {
+        &self.prefix_hint
+    }
+
+    fn routes(&self) -> Vec<RouteInfo> {
+        self.inner
+            .routes()
+            .into_iter()
+            .map(|info| RouteInfo {
+                path: format!("{}{}", self.prefix, info.path),
+                ..info
+            })
+            .collect()
+    }
+
+    fn match_request(&self, head: &RequestHead<'_>) -> bool {
+        self.strip_prefix(head.path())
+            .map(|rest| self.inner.match_request(&head.with_path(rest)))
+            .unwrap_or(false)
+    }
+
+    fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        self.strip_prefix(head.path())
+            .map(|rest| self.inner.allowed_methods(&head.with_path(rest)))
+            .unwrap_or_default()
+    }
+
+    fn explicit_head(&self, head: &RequestHead<'_>) -> bool {
+        self.strip_prefix(head.path())
+            .map(|rest| self.inner.explicit_head(&head.with_path(rest)))
+            .unwrap_or(false)
+    }
+
+    async fn respond(
+        &self,
+        ctx: &Self::Ctx,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, NailsError> {
+        let (mut parts, body) = req.into_parts();
+        let rest = self
+            .strip_prefix(parts.uri.path())
+            .expect("internal error: invalid path given");
+        let path_and_query = if let Some(query) = parts.uri.query() {
+            format!("{}?{}", rest, query)
+        } else {
+            rest.to_owned()
+        };
+        let mut uri_parts = parts.uri.into_parts();
+        uri_parts.path_and_query = Some(
+            path_and_query
+                .parse::<PathAndQuery>()
+                .expect("internal error: invalid path given"),
+        );
+        parts.uri = Uri::from_parts(uri_parts).expect("internal error: invalid path given");
+        self.inner
+            .respond(ctx, Request::from_parts(parts, body))
+            .await
+    }
+}
+
+/// Restricts a route to the hosts matching a pattern.
+pub struct HostRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    host: String,
+    shape: HostShape,
+    inner: SharedRoute<Ctx>,
+}
+
+impl<Ctx> HostRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    fn new(host: &str, inner: SharedRoute<Ctx>) -> Self {
+        Self {
+            host: host.to_owned(),
+            shape: HostShape::parse(host),
+            inner,
+        }
+    }
+
+    fn matches(&self, head: &RequestHead<'_>) -> bool {
+        head.host()
+            .map(|host| self.shape.matches(host))
+            .unwrap_or(false)
+    }
+}
+
+impl<Ctx> fmt::Debug for HostRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HostRoute")
+            .field("host", &self.host)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<Ctx> Routable for HostRoute<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    type Ctx = Ctx;
+
+    fn path_prefix_hint(&self) -> &str {
+        self.inner.path_prefix_hint()
+    }
+
+    fn routes(&self) -> Vec<RouteInfo> {
+        self.inner
+            .routes()
+            .into_iter()
+            .map(|info| RouteInfo {
+                host: Some(info.host.unwrap_or_else(|| self.host.clone())),
+                ..info
+            })
+            .collect()
+    }
+
+    fn match_request(&self, head: &RequestHead<'_>) -> bool {
+        self.matches(head) && self.inner.match_request(head)
+    }
+
+    fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        if self.matches(head) {
+            self.inner.allowed_methods(head)
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn explicit_head(&self, head: &RequestHead<'_>) -> bool {
+        self.matches(head) && self.inner.explicit_head(head)
+    }
+
+    async fn respond(
+        &self,
+        ctx: &Self::Ctx,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, NailsError> {
+        self.inner.respond(ctx, req).await
+    }
+}
+
 pub struct FunctionRoute<Ctx, F, Req> {
     f: F,
     _marker: PhantomData<fn(Ctx, Req)>,
@@ -141,8 +767,33 @@ where
         Req::path_prefix_hint()
     }
 
-    fn match_path(&self, method: &Method, path: &str) -> bool {
-        Req::match_path(method, path)
+    fn routes(&self) -> Vec<RouteInfo> {
+        Req::route_patterns()
+            .into_iter()
+            .map(|pattern| RouteInfo {
+                methods: pattern.methods,
+                host: pattern.host.map(str::to_owned),
+                path: pattern.path.to_owned(),
+                request_type: std::any::type_name::<Req>(),
+                handler: Some(std::any::type_name::<F>()),
+            })
+            .collect()
+    }
+
+    fn match_request(&self, head: &RequestHead<'_>) -> bool {
+        Req::match_path(head.method(), head.path()) && Req::match_head(head)
+    }
+
+    fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+        if Req::match_head(head) {
+            Req::allowed_methods(head.path())
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn explicit_head(&self, head: &RequestHead<'_>) -> bool {
+        Req::explicit_head(head)
     }
 
     async fn respond(
@@ -154,3 +805,369 @@ where
         (self.f)(ctx.clone(), req).await
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[derive(Debug)]
+    struct ExactRoute(&'static str);
+
+    #[async_trait]
+    impl Routable for ExactRoute {
+        type Ctx = ();
+
+        fn path_prefix_hint(&self) -> &str {
+            self.0
+        }
+        fn match_request(&self, head: &RequestHead<'_>) -> bool {
+            head.path() == self.0
+        }
+        async fn respond(
+            &self,
+            _ctx: &Self::Ctx,
+            _req: Request<Body>,
+        ) -> Result<Response<Body>, NailsError> {
+            Ok(Response::new(Body::from(self.0)))
+        }
+    }
+
+    #[derive(Debug)]
+    struct PatternRoute(&'static str, Method);
+
+    #[async_trait]
+    impl Routable for PatternRoute {
+        type Ctx = ();
+
+        fn routes(&self) -> Vec<RouteInfo> {
+            vec![RouteInfo {
+                methods: vec![self.1.clone()],
+                host: None,
+                path: self.0.to_owned(),
+                request_type: self.0,
+                handler: None,
+            }]
+        }
+        fn path_prefix_hint(&self) -> &str {
+            &self.0[..self.0.find('{').unwrap_or(self.0.len())]
+        }
+        fn match_request(&self, head: &RequestHead<'_>) -> bool {
+            *head.method() == self.1 && !self.allowed_methods(head).is_empty()
+        }
+        fn allowed_methods(&self, head: &RequestHead<'_>) -> Vec<Method> {
+            let path = head.path();
+            let matches = path.split('/').count() == self.0.split('/').count()
+                && path
+                    .split('/')
+                    .zip(self.0.split('/'))
+                    .all(|(comp, pat)| comp == pat || pat.starts_with('{'));
+            if matches {
+                vec![self.1.clone()]
+            } else {
+                Vec::new()
+            }
+        }
+        async fn respond(
+            &self,
+            _ctx: &Self::Ctx,
+            _req: Request<Body>,
+        ) -> Result<Response<Body>, NailsError> {
+            Ok(Response::new(Body::from(_req.uri().to_string())))
+        }
+    }
+
+    fn find(router: &Router<()>, path: &str) -> Option<*const ()> {
+        router
+            .find_route(&RequestHead::new(&Method::GET, path))
+            .map(|route| route as *const _ as *const ())
+    }
+
+    #[test]
+    fn test_find_route() {
+        let mut router = Router::new();
+        router.add_route(ExactRoute("/api/posts"));
+        router.add_route(ExactRoute("/api/articles"));
+        router.add_route(ExactRoute("/api/articles/feed"));
+        let routes = router
+            .routes
+            .iter()
+            .map(|route| &**route as *const _ as *const ())
+            .collect::<Vec<_>>();
+
+        assert_eq!(router.path_prefix_hint(), "/api/");
+        assert_eq!(find(&router, "/api/posts"), Some(routes[0]));
+        assert_eq!(find(&router, "/api/articles"), Some(routes[1]));
+        assert_eq!(find(&router, "/api/articles/feed"), Some(routes[2]));
+        assert_eq!(find(&router, "/api/articles/"), None);
+        assert_eq!(find(&router, "/api/tags"), None);
+    }
+
+    fn conflict_router() -> Router<()> {
+        let mut router = Router::new();
+        router.add_route(PatternRoute("/api/articles/{slug}", Method::GET));
+        router.add_route(PatternRoute("/api/articles/feed", Method::GET));
+        router.add_route(PatternRoute("/api/articles/feed", Method::POST));
+        router
+    }
+
+    fn find_pattern(router: &Router<()>, path: &str) -> Option<String> {
+        router
+            .find_route(&RequestHead::new(&Method::GET, path))
+            .map(|route| route.routes()[0].path.clone())
+    }
+
+    fn allowed_methods(router: &Router<()>, path: &str) -> Vec<Method> {
+        router.allowed_methods(&RequestHead::new(&Method::GET, path))
+    }
+
+    #[test]
+    fn test_conflict_error() {
+        let mut router = conflict_router();
+        let e = router.resolve_conflicts(ConflictPolicy::Error).unwrap_err();
+        assert_eq!(e.conflicts.len(), 1);
+        assert_eq!(
+            e.to_string(),
+            "ambiguous routes:\n    \
+             GET /api/articles/{slug} (/api/articles/{slug}) and \
+             GET /api/articles/feed (/api/articles/feed)",
+        );
+    }
+
+    #[test]
+    fn test_conflict_static_first() {
+        let mut router = conflict_router();
+        router
+            .resolve_conflicts(ConflictPolicy::StaticFirst)
+            .unwrap();
+        assert_eq!(
+            find_pattern(&router, "/api/articles/feed")
+                .as_ref()
+                .map(|s| &s[..]),
+            Some("/api/articles/feed"),
+        );
+        assert_eq!(
+            find_pattern(&router, "/api/articles/foo")
+                .as_ref()
+                .map(|s| &s[..]),
+            Some("/api/articles/{slug}"),
+        );
+
+        let mut router = conflict_router();
+        router.add_route(PatternRoute("/api/articles/{id}", Method::GET));
+        let e = router
+            .resolve_conflicts(ConflictPolicy::StaticFirst)
+            .unwrap_err();
+        assert_eq!(e.conflicts.len(), 1);
+        assert_eq!(e.conflicts[0].first.path, "/api/articles/{slug}");
+        assert_eq!(e.conflicts[0].second.path, "/api/articles/{id}");
+    }
+
+    #[test]
+    fn test_conflict_registration_order() {
+        let mut router = conflict_router();
+        router
+            .resolve_conflicts(ConflictPolicy::RegistrationOrder)
+            .unwrap();
+        assert_eq!(
+            find_pattern(&router, "/api/articles/feed")
+                .as_ref()
+                .map(|s| &s[..]),
+            Some("/api/articles/{slug}"),
+        );
+    }
+
+    #[test]
+    fn test_conflict_nested_router() {
+        let nested = |patterns: &[&'static str]| {
+            let mut inner = Router::new();
+            for &pattern in patterns {
+                inner.add_route(PatternRoute(pattern, Method::GET));
+            }
+            let mut router = Router::new();
+            router.add_route(PatternRoute("/api/tags", Method::GET));
+            router.add_route(inner);
+            router
+        };
+
+        let mut router = nested(&["/api/articles/{slug}", "/api/articles/feed"]);
+        let e = router.resolve_conflicts(ConflictPolicy::Error).unwrap_err();
+        assert_eq!(e.conflicts.len(), 1);
+        let e = router
+            .resolve_conflicts(ConflictPolicy::StaticFirst)
+            .unwrap_err();
+        assert_eq!(e.conflicts.len(), 1);
+        assert_eq!(e.conflicts[0].first.path, "/api/articles/{slug}");
+        assert_eq!(e.conflicts[0].second.path, "/api/articles/feed");
+        router
+            .resolve_conflicts(ConflictPolicy::RegistrationOrder)
+            .unwrap();
+
+        let mut router = nested(&["/api/articles/feed", "/api/articles/{slug}"]);
+        router
+            .resolve_conflicts(ConflictPolicy::StaticFirst)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        let mut router = conflict_router();
+        router.add_route(PatternRoute("/api/articles/{slug}", Method::DELETE));
+        assert_eq!(
+            allowed_methods(&router, "/api/articles/feed"),
+            vec![Method::GET, Method::POST, Method::DELETE],
+        );
+        assert_eq!(
+            allowed_methods(&router, "/api/articles/foo"),
+            vec![Method::GET, Method::DELETE],
+        );
+        assert!(allowed_methods(&router, "/api/tags").is_empty());
+    }
+
+    fn body_string(resp: Response<Body>) -> String {
+        block_on(async {
+            let mut body = resp.into_body();
+            let mut buf = Vec::new();
+            while let Some(chunk) = body.next().await {
+                buf.extend_from_slice(chunk.unwrap().as_ref());
+            }
+            String::from_utf8(buf).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_mount() {
+        let mut users = Router::new();
+        users.add_route(PatternRoute("/users", Method::POST));
+        users.add_route(PatternRoute("/users/{id}", Method::GET));
+        let mut api = Router::new();
+        api.mount("/v1", users);
+        let mut router = Router::new();
+        router.add_route(PatternRoute("/", Method::GET));
+        router.mount("/api/", api);
+
+        let paths = router
+            .routes()
+            .into_iter()
+            .map(|info| info.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/", "/api/v1/users", "/api/v1/users/{id}"]);
+        assert_eq!(router.routes[2].path_prefix_hint(), "/api/v1/users/");
+        assert_eq!(
+            find_pattern(&router, "/api/v1/users/42")
+                .as_ref()
+                .map(|s| &s[..]),
+            Some("/api/v1/users/{id}"),
+        );
+        assert!(find_pattern(&router, "/api/v1/users").is_none());
+        assert!(find_pattern(&router, "/api/v1users/42").is_none());
+        assert!(find_pattern(&router, "/users/42").is_none());
+        assert_eq!(
+            allowed_methods(&router, "/api/v1/users"),
+            vec![Method::POST]
+        );
+
+        let req = Request::builder()
+            .uri("/api/v1/users/42?foo=bar")
+            .body(Body::empty())
+            .unwrap();
+        let resp = block_on(router.respond(&(), req)).unwrap();
+        assert_eq!(body_string(resp), "/users/42?foo=bar");
+    }
+
+    fn find_host(router: &Router<()>, host: &str, path: &str) -> Option<String> {
+        router
+            .find_route(&RequestHead::new(&Method::GET, path).with_host(Some(host)))
+            .map(|route| route.routes()[0].host.clone().unwrap_or_default())
+    }
+
+    #[test]
+    fn test_mount_host() {
+        let users = || {
+            let mut users = Router::new();
+            users.add_route(PatternRoute("/users/{id}", Method::GET));
+            users
+        };
+        let mut router = Router::new();
+        router.mount_host("api.example.com", users());
+        router.mount_host("{tenant}.admin.example.com", users());
+        router.resolve_conflicts(ConflictPolicy::Error).unwrap();
+
+        assert_eq!(
+            router.routes()[1].to_string(),
+            "GET {tenant}.admin.example.com/users/{id} (/users/{id})",
+        );
+        assert_eq!(
+            find_host(&router, "API.example.com", "/users/42").as_deref(),
+            Some("api.example.com"),
+        );
+        assert_eq!(
+            find_host(&router, "acme.admin.example.com", "/users/42").as_deref(),
+            Some("{tenant}.admin.example.com"),
+        );
+        assert_eq!(find_host(&router, "example.com", "/users/42"), None);
+        assert!(allowed_methods(&router, "/users/42").is_empty());
+
+        let mut router = Router::new();
+        router.add_route(PatternRoute("/users/{id}", Method::GET));
+        router.mount_host("{tenant}.example.com", users());
+        router.mount_host("api.example.com", users());
+        let e = router.resolve_conflicts(ConflictPolicy::Error).unwrap_err();
+        assert_eq!(e.conflicts.len(), 3);
+        let e = router
+            .resolve_conflicts(ConflictPolicy::StaticFirst)
+            .unwrap_err();
+        assert_eq!(e.conflicts.len(), 1);
+        assert_eq!(
+            find_host(&router, "www.example.com", "/users/42").as_deref(),
+            Some("{tenant}.example.com"),
+        );
+        assert_eq!(
+            find_host(&router, "example.org", "/users/42").as_deref(),
+            Some(""),
+        );
+    }
+
+    #[derive(Debug, Clone)]
+    struct AppCtx;
+
+    impl Context for AppCtx {}
+
+    impl AsContext<()> for AppCtx {
+        fn as_context(&self) -> std::borrow::Cow<'_, ()> {
+            std::borrow::Cow::Owned(())
+        }
+    }
+
+    #[test]
+    fn test_mount_sub_context() {
+        let mut users = Router::<()>::new();
+        users.add_route(PatternRoute("/users/{id}", Method::GET));
+        let mut router = Router::<AppCtx>::new();
+        router.mount("/api", users);
+
+        let req = Request::builder()
+            .uri("/api/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let route = router.find_route(&RequestHead::from_request(&req)).unwrap();
+        let resp = block_on(route.respond(&AppCtx, req)).unwrap();
+        assert_eq!(body_string(resp), "/users/42");
+    }
+
+    #[test]
+    #[should_panic(expected = "mount prefix must start with slash")]
+    fn test_mount_relative_prefix() {
+        Router::<()>::new().mount("api", Router::new());
+    }
+
+    #[test]
+    fn test_prefix_hint_char_boundary() {
+        let mut router = Router::new();
+        router.add_route(ExactRoute("/あ"));
+        router.add_route(ExactRoute("/い"));
+        assert_eq!(router.path_prefix_hint(), "/");
+    }
+}
@@ -0,0 +1,265 @@
+//! Runtime view of path patterns like `/api/posts/{id}`, used to analyze the route table.
+
+use std::cmp::Ordering;
+
+use nails_pattern::{Component, Variable};
+
+use crate::request::{match_host, split_path_component, PathConstraint};
+
+#[derive(Debug)]
+pub(crate) struct PathShape {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Literal(String),
+    /// Variables mixed with literals, like `{name}.{ext}`.
+    Partial {
+        literals: Vec<String>,
+        constraints: Vec<Option<PathConstraint>>,
+    },
+    Var(Option<PathConstraint>),
+    /// Catch-all variable like `{*rest}`, always the last segment.
+    Rest,
+}
+
+impl PathShape {
+    pub(crate) fn parse(pattern: &str) -> Self {
+        let segments = match nails_pattern::parse_path(pattern) {
+            Ok(components) => components.into_iter().map(Segment::from).collect(),
+            // Patterns of derived routes are valid; compare others literally.
+            Err(_) => pattern
+                .strip_prefix('/')
+                .unwrap_or(pattern)
+                .split('/')
+                .map(|seg| Segment::Literal(seg.to_owned()))
+                .collect(),
+        };
+        Self { segments }
+    }
+
+    /// Returns true if there is a path matched by both patterns.
+    pub(crate) fn overlaps(&self, other: &Self) -> bool {
+        for i in 0.. {
+            match (self.segments.get(i), other.segments.get(i)) {
+                (Some(Segment::Rest), Some(_)) | (Some(_), Some(Segment::Rest)) => return true,
+                (Some(Segment::Literal(lhs)), Some(Segment::Literal(rhs))) if lhs != rhs => {
+                    return false
+                }
+                (Some(Segment::Literal(literal)), Some(Segment::Var(Some(constraint))))
+                | (Some(Segment::Var(Some(constraint))), Some(Segment::Literal(literal)))
+                    if !constraint.matches(literal) =>
+                {
+                    return false
+                }
+                (
+                    Some(Segment::Literal(literal)),
+                    Some(Segment::Partial {
+                        literals,
+                        constraints,
+                    }),
+                )
+                | (
+                    Some(Segment::Partial {
+                        literals,
+                        constraints,
+                    }),
+                    Some(Segment::Literal(literal)),
+                ) if !matches_partial(literal, literals, constraints) => return false,
+                (
+                    Some(Segment::Partial { literals: lhs, .. }),
+                    Some(Segment::Partial { literals: rhs, .. }),
+                ) if !partials_overlap(lhs, rhs) => return false,
+                (Some(_), Some(_)) => {}
+                (None, None) => return true,
+                (Some(_), None) | (None, Some(_)) => return false,
+            }
+        }
+        unreachable!()
+    }
+
+    /// Compares specificity: the pattern with a literal at the leftmost
+    /// differing position comes first.
+    pub(crate) fn cmp_specificity(&self, other: &Self) -> Ordering {
+        self.segments
+            .iter()
+            .map(Segment::rank)
+            .cmp(other.segments.iter().map(Segment::rank))
+    }
+}
+
+impl From<Component> for Segment {
+    fn from(component: Component) -> Self {
+        let constraint = |var: Variable| var.constraint.map(PathConstraint::from_parsed);
+        match component {
+            Component::Literal(literal) => Segment::Literal(literal),
+            Component::Var(var) => Segment::Var(constraint(var)),
+            Component::Rest(_) => Segment::Rest,
+            Component::Partial { literals, vars } => Segment::Partial {
+                literals,
+                constraints: vars.into_iter().map(constraint).collect(),
+            },
+        }
+    }
+}
+
+impl Segment {
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 0,
+            Segment::Partial { .. } => 1,
+            Segment::Var(Some(_)) => 2,
+            Segment::Var(None) => 3,
+            Segment::Rest => 4,
+        }
+    }
+}
+
+fn matches_partial(
+    literal: &str,
+    literals: &[String],
+    constraints: &[Option<PathConstraint>],
+) -> bool {
+    let literals = literals.iter().map(String::as_str).collect::<Vec<_>>();
+    if let Some(values) = split_path_component(literal, &literals) {
+        values
+            .iter()
+            .zip(constraints)
+            .all(|(value, constraint)| match constraint {
+                Some(constraint) => constraint.matches(value),
+                None => true,
+            })
+    } else {
+        false
+    }
+}
+
+// Conservative: only the leading and trailing literals are compared.
+fn partials_overlap(lhs: &[String], rhs: &[String]) -> bool {
+    let (lhs_first, lhs_last) = (&lhs[0], &lhs[lhs.len() - 1]);
+    let (rhs_first, rhs_last) = (&rhs[0], &rhs[rhs.len() - 1]);
+    (lhs_first.starts_with(rhs_first.as_str()) || rhs_first.starts_with(lhs_first.as_str()))
+        && (lhs_last.ends_with(rhs_last.as_str()) || rhs_last.ends_with(lhs_last.as_str()))
+}
+
+/// Host patterns like `{tenant}.example.com`. A `{...}` label matches any label.
+#[derive(Debug, Clone)]
+pub(crate) struct HostShape {
+    labels: Vec<Option<String>>,
+}
+
+impl HostShape {
+    pub(crate) fn parse(pattern: &str) -> Self {
+        let labels = pattern
+            .split('.')
+            .map(|label| {
+                if label.starts_with('{') && label.ends_with('}') {
+                    None
+                } else {
+                    Some(label.to_ascii_lowercase())
+                }
+            })
+            .collect();
+        Self { labels }
+    }
+
+    pub(crate) fn matches(&self, host: &str) -> bool {
+        let labels = self.labels.iter().map(Option::as_deref).collect::<Vec<_>>();
+        match_host(host, &labels).is_some()
+    }
+
+    /// Returns true if there is a host matched by both patterns.
+    pub(crate) fn overlaps(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(&other.labels)
+                .all(|(lhs, rhs)| match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) => lhs == rhs,
+                    _ => true,
+                })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlaps() {
+        let parse = PathShape::parse;
+        assert!(parse("/api/articles/feed").overlaps(&parse("/api/articles/{slug}")));
+        assert!(parse("/api/{a}/x").overlaps(&parse("/api/x/{b}")));
+        assert!(parse("/").overlaps(&parse("/{id}")));
+        assert!(!parse("/api/articles").overlaps(&parse("/api/articles/{slug}")));
+        assert!(!parse("/api/articles/feed").overlaps(&parse("/api/tags/{slug}")));
+        assert!(parse("/static/{*path}").overlaps(&parse("/static/js/app.js")));
+        assert!(parse("/static/{*path}").overlaps(&parse("/static/")));
+        assert!(parse("/{*path}").overlaps(&parse("/static/{*path}")));
+        assert!(!parse("/static/{*path}").overlaps(&parse("/static")));
+        assert!(!parse("/static/{*path}").overlaps(&parse("/api/{*path}")));
+        assert!(parse("/files/{name}.{ext}").overlaps(&parse("/files/a.txt")));
+        assert!(parse("/files/{name}.{ext}").overlaps(&parse("/files/{id}")));
+        assert!(parse("/files/{name}.{ext}").overlaps(&parse("/files/{name}.txt")));
+        assert!(!parse("/files/{name}.{ext}").overlaps(&parse("/files/README")));
+        assert!(!parse("/v{version}/items").overlaps(&parse("/api/items")));
+        assert!(!parse("/v{version}/items").overlaps(&parse("/x{version}/items")));
+        assert!(!parse("/{{{id}}}").overlaps(&parse("/{{")));
+        assert!(parse("/{{{id}}}").overlaps(&parse("/{{1}}")));
+        assert!(!parse("/api/articles/feed").overlaps(&parse("/api/articles/{slug:!feed}")));
+        assert!(parse("/api/articles/feeds").overlaps(&parse("/api/articles/{slug:!feed}")));
+        assert!(!parse("/api/articles/feed").overlaps(&parse("/api/articles/{id:\\d{1,8}}")));
+        assert!(parse("/api/articles/42").overlaps(&parse("/api/articles/{id:\\d{1,8}}")));
+        assert!(!parse("/files/a.txt").overlaps(&parse("/files/{name}.{ext:md|html}")));
+        assert!(parse("/files/a.md").overlaps(&parse("/files/{name}.{ext:md|html}")));
+    }
+
+    #[test]
+    fn test_cmp_specificity() {
+        let parse = PathShape::parse;
+        assert_eq!(
+            parse("/api/articles/feed").cmp_specificity(&parse("/api/articles/{slug}")),
+            Ordering::Less,
+        );
+        assert_eq!(
+            parse("/api/{a}/x").cmp_specificity(&parse("/api/x/{b}")),
+            Ordering::Greater,
+        );
+        assert_eq!(
+            parse("/api/{a}").cmp_specificity(&parse("/api/{b}")),
+            Ordering::Equal,
+        );
+        assert_eq!(
+            parse("/static/{path}").cmp_specificity(&parse("/static/{*path}")),
+            Ordering::Less,
+        );
+        assert_eq!(
+            parse("/files/{name}.{ext}").cmp_specificity(&parse("/files/{id}")),
+            Ordering::Less,
+        );
+        assert_eq!(
+            parse("/files/{id:\\d+}").cmp_specificity(&parse("/files/{name}")),
+            Ordering::Less,
+        );
+        assert_eq!(
+            parse("/{*path}").cmp_specificity(&parse("/static/{*path}")),
+            Ordering::Greater,
+        );
+    }
+
+    #[test]
+    fn test_host_shape() {
+        let parse = HostShape::parse;
+        assert!(parse("api.example.com").matches("API.example.com"));
+        assert!(parse("{tenant}.example.com").matches("acme.example.com"));
+        assert!(!parse("{tenant}.example.com").matches("example.com"));
+        assert!(!parse("{tenant}.example.com").matches(".example.com"));
+        assert!(parse("{tenant}.example.com").overlaps(&parse("api.example.com")));
+        assert!(parse("api.Example.com").overlaps(&parse("api.example.com")));
+        assert!(!parse("api.example.com").overlaps(&parse("admin.example.com")));
+        assert!(!parse("{tenant}.example.com").overlaps(&parse("example.com")));
+    }
+}
@@ -0,0 +1,135 @@
+//! Radix tree keyed on path prefixes, used to narrow down route candidates.
+
+#[derive(Debug, Clone)]
+pub(crate) struct RadixTree<T> {
+    root: Node<T>,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    label: Vec<u8>,
+    values: Vec<T>,
+    children: Vec<Node<T>>,
+}
+
+impl<T> RadixTree<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            root: Node {
+                label: Vec::new(),
+                values: Vec::new(),
+                children: Vec::new(),
+            },
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, value: T) {
+        self.root.insert(key.as_bytes(), value);
+    }
+
+    /// Visits every value whose key is a prefix of `path`, shortest key first.
+    pub(crate) fn for_each_prefix<'a, F>(&'a self, path: &str, mut f: F)
+    where
+        F: FnMut(&'a T),
+    {
+        let mut node = &self.root;
+        let mut rest = path.as_bytes();
+        loop {
+            node.values.iter().for_each(&mut f);
+            let child = node
+                .children
+                .iter()
+                .find(|child| rest.starts_with(&child.label));
+            if let Some(child) = child {
+                rest = &rest[child.label.len()..];
+                node = child;
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+impl<T> Default for RadixTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Node<T> {
+    fn insert(&mut self, key: &[u8], value: T) {
+        if key.is_empty() {
+            self.values.push(value);
+            return;
+        }
+        let pos = self
+            .children
+            .iter()
+            .position(|child| child.label[0] == key[0]);
+        let pos = if let Some(pos) = pos {
+            pos
+        } else {
+            self.children.push(Node {
+                label: key.to_owned(),
+                values: vec![value],
+                children: Vec::new(),
+            });
+            return;
+        };
+        let child = &mut self.children[pos];
+        let common = common_prefix_len(&child.label, key);
+        if common < child.label.len() {
+            // Split the edge so that the common part becomes its own node.
+            let suffix = child.label.split_off(common);
+            let grandchild = Node {
+                label: suffix,
+                values: std::mem::take(&mut child.values),
+                children: std::mem::take(&mut child.children),
+            };
+            child.children.push(grandchild);
+        }
+        child.insert(&key[common..], value);
+    }
+}
+
+pub(crate) fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    fn prefixes(tree: &RadixTree<u32>, path: &str) -> Vec<u32> {
+        let mut values = Vec::new();
+        tree.for_each_prefix(path, |&v| values.push(v));
+        values
+    }
+
+    #[test]
+    fn test_radix_tree() {
+        let mut tree = RadixTree::new();
+        tree.insert("/api/posts/", 0);
+        tree.insert("/api/posts", 1);
+        tree.insert("/api/articles/feed", 2);
+        tree.insert("/api/articles/", 3);
+        tree.insert("", 4);
+        tree.insert("/", 5);
+        tree.insert("/api/posts/", 6);
+
+        assert_eq!(prefixes(&tree, "/api/posts/1"), vec![4, 5, 1, 0, 6]);
+        assert_eq!(prefixes(&tree, "/api/posts"), vec![4, 5, 1]);
+        assert_eq!(prefixes(&tree, "/api/articles/feed"), vec![4, 5, 3, 2]);
+        assert_eq!(prefixes(&tree, "/api/articles/foo"), vec![4, 5, 3]);
+        assert_eq!(prefixes(&tree, "/api/tags"), vec![4, 5]);
+        assert_eq!(prefixes(&tree, "foo"), vec![4]);
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        assert_eq!(common_prefix_len(b"/api/posts", b"/api/articles"), 5);
+        assert_eq!(common_prefix_len(b"", b"/api"), 0);
+        assert_eq!(common_prefix_len(b"/api", b"/api"), 4);
+    }
+}
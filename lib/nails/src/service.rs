@@ -1,15 +1,19 @@
 use futures::prelude::*;
 
+use std::fmt;
 use std::sync::Arc;
 
-use contextful::Context;
+use contextful::{AsContext, Context};
+use futures::future::BoxFuture;
 use futures::task::Poll;
+use hyper::body::Payload;
 use hyper::client::service::Service as HyperService;
-use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
 
-use crate::error::NailsError;
-use crate::request::Preroute;
-use crate::routing::{Routable, Router};
+use crate::error::{NailsError, RouteConflictError};
+use crate::request::{Preroute, RequestHead};
+use crate::routing::{ConflictPolicy, Routable, RouteInfo, Router};
+use crate::session::{SessionConfig, SessionKey, SessionLoader, SessionStore};
 
 #[derive(Debug)]
 pub struct ServiceWithContext<Ctx>
@@ -92,6 +96,11 @@ where
         Builder::new()
     }
 
+    /// Lists every route, including mounted ones, in the order they are tried.
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.inner.router.routes()
+    }
+
     pub fn with_context(self, ctx: &Ctx) -> ServiceWithContext<Ctx> {
         ServiceWithContext {
             service: self,
@@ -117,6 +126,7 @@ where
     Ctx: Context + Send + Sync + 'static,
 {
     inner: Option<ServiceInner<Ctx>>,
+    conflict_policy: ConflictPolicy,
 }
 
 impl<Ctx: Context> Builder<Ctx>
@@ -127,15 +137,31 @@ where
         Self {
             inner: Some(ServiceInner {
                 router: Router::new(),
+                path_normalization: PathNormalization::default(),
+                fallback: None,
+                session: None,
             }),
+            conflict_policy: ConflictPolicy::default(),
         }
     }
 
+    /// Builds the service.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the routes are ambiguous under the configured
+    /// [`ConflictPolicy`](ConflictPolicy). Use [`try_finish`](Builder::try_finish)
+    /// to handle it as an error.
     pub fn finish(&mut self) -> Service<Ctx> {
-        let inner = self.inner.take().expect("Builder::finish called twice");
-        Service {
+        self.try_finish().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_finish(&mut self) -> Result<Service<Ctx>, RouteConflictError> {
+        let mut inner = self.inner.take().expect("Builder::finish called twice");
+        inner.router.resolve_conflicts(self.conflict_policy)?;
+        Ok(Service {
             inner: Arc::new(inner),
-        }
+        })
     }
 
     fn inner_mut(&mut self) -> &mut ServiceInner<Ctx> {
@@ -144,6 +170,41 @@ where
             .expect("this builder is already finished")
     }
 
+    pub fn conflict_policy(&mut self, policy: ConflictPolicy) -> &mut Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    pub fn path_normalization(&mut self, policy: PathNormalization) -> &mut Self {
+        self.inner_mut().path_normalization = policy;
+        self
+    }
+
+    /// Sets the handler for requests no route matches, instead of the plain-text 404.
+    ///
+    /// It isn't called when a route matches the path but not the method;
+    /// such requests are still answered with `405 Method Not Allowed`.
+    pub fn fallback<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(Ctx, Request<Body>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<Body>, NailsError>> + Send + 'static,
+    {
+        self.inner_mut().fallback = Some(Box::new(move |ctx, req| handler(ctx, req).boxed()));
+        self
+    }
+
+    /// Enables sessions kept in `store`, with cookies protected by `key`.
+    ///
+    /// Returns the session settings for further configuration.
+    pub fn session<S>(&mut self, key: SessionKey, store: S) -> &mut SessionConfig
+    where
+        S: SessionStore,
+    {
+        let inner = self.inner_mut();
+        inner.session = Some(SessionConfig::new(key, Arc::new(store)));
+        inner.session.as_mut().unwrap()
+    }
+
     pub fn add_route<R>(&mut self, route: R) -> &mut Self
     where
         R: Routable<Ctx = Ctx> + Send + Sync + 'static,
@@ -161,14 +222,97 @@ where
         self.inner_mut().router.add_function_route(route);
         self
     }
+
+    pub fn add_subcontext_function_route<SubCtx, F, Fut, Req>(&mut self, route: F) -> &mut Self
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+        F: Fn(SubCtx, Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<Body>, NailsError>> + Send + 'static,
+        Req: Preroute + Send + 'static,
+    {
+        self.inner_mut().router.add_subcontext_function_route(route);
+        self
+    }
+
+    pub fn mount<SubCtx>(&mut self, prefix: &str, router: Router<SubCtx>) -> &mut Self
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        self.inner_mut().router.mount(prefix, router);
+        self
+    }
+
+    /// Adds the routes of `router`, served only for the hosts matching `host`.
+    ///
+    /// See [`Router::mount_host`](Router::mount_host) for the pattern syntax.
+    pub fn mount_host<SubCtx>(&mut self, host: &str, router: Router<SubCtx>) -> &mut Self
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        self.inner_mut().router.mount_host(host, router);
+        self
+    }
+
+    /// Adds the routes of another service under `prefix`.
+    ///
+    /// Only the routes are taken over; the rest of its configuration is ignored.
+    pub fn mount_service<SubCtx>(&mut self, prefix: &str, service: &Service<SubCtx>) -> &mut Self
+    where
+        SubCtx: Context + Send + Sync + 'static,
+        Ctx: AsContext<SubCtx>,
+    {
+        let routes = service.inner.router.sorted_routes();
+        self.inner_mut().router.mount_routes(prefix, routes);
+        self
+    }
 }
 
-#[derive(Debug)]
+/// How request paths that aren't in canonical form are treated.
+///
+/// The canonical form has no empty segments (`//`) and no `.` or `..` segments.
+/// A trailing slash is added or removed if that's the only way to reach a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathNormalization {
+    /// Responds with `308 Permanent Redirect` to the canonical path.
+    Redirect,
+    /// Routes the request as if the canonical path was requested.
+    Normalize,
+    /// Routes the path as is.
+    #[default]
+    Strict,
+}
+
+type Fallback<Ctx> = Box<
+    dyn Fn(Ctx, Request<Body>) -> BoxFuture<'static, Result<Response<Body>, NailsError>>
+        + Send
+        + Sync,
+>;
+
 struct ServiceInner<Ctx>
 where
     Ctx: Context + Send + Sync + 'static,
 {
     router: Router<Ctx>,
+    path_normalization: PathNormalization,
+    fallback: Option<Fallback<Ctx>>,
+    session: Option<SessionConfig>,
+}
+
+impl<Ctx> fmt::Debug for ServiceInner<Ctx>
+where
+    Ctx: Context + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServiceInner")
+            .field("router", &self.router)
+            .field("path_normalization", &self.path_normalization)
+            .field("fallback", &self.fallback.as_ref().map(|_| ".."))
+            .field("session", &self.session)
+            .finish()
+    }
 }
 
 impl<Ctx> ServiceInner<Ctx>
@@ -191,16 +335,85 @@ where
                 .body(Body::empty())
                 .unwrap());
         }
-        let resp = if self.router.match_path(req.method(), req.uri().path()) {
-            match self.router.respond(ctx, req).await {
+        let req = if let Some(path) = self.canonical_path(&RequestHead::from_request(&req)) {
+            match self.path_normalization {
+                PathNormalization::Redirect => {
+                    let location = match req.uri().query() {
+                        Some(query) => format!("{}?{}", path, query),
+                        None => path,
+                    };
+                    return Ok(permanent_redirect(&location));
+                }
+                PathNormalization::Normalize => replace_path(req, &path),
+                PathNormalization::Strict => req,
+            }
+        } else {
+            req
+        };
+        let (req, session) = if let Some(config) = &self.session {
+            let loader = config.loader(&req);
+            let mut req = req;
+            req.extensions_mut().insert(loader.clone());
+            (req, Some(loader))
+        } else {
+            (req, None)
+        };
+        let head = RequestHead::from_request(&req);
+        let route = self.router.find_route(&head);
+        let resp = if let Some(route) = route {
+            let is_head = req.method() == Method::HEAD;
+            // Routes listing HEAD in their `method` answer it by themselves;
+            // others are GET handlers whose body we drop below.
+            let explicit_head = is_head && route.explicit_head(&head);
+            let resp = match route.respond(ctx, req).await {
                 Ok(resp) => resp,
                 Err(e) => e.to_response(),
+            };
+            if is_head {
+                strip_body(resp, !explicit_head)
+            } else {
+                resp
             }
         } else {
-            Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .body(Body::from("Not Found"))
-                .unwrap()
+            let mut allowed_methods = self.router.allowed_methods(&head);
+            if allowed_methods.is_empty() {
+                if let Some(fallback) = &self.fallback {
+                    let is_head = req.method() == Method::HEAD;
+                    let resp = match fallback(ctx.clone(), req).await {
+                        Ok(resp) => resp,
+                        Err(e) => e.to_response(),
+                    };
+                    if is_head {
+                        strip_body(resp, true)
+                    } else {
+                        resp
+                    }
+                } else {
+                    not_found()
+                }
+            } else {
+                // OPTIONS is synthesized below unless a route handles it explicitly.
+                if !allowed_methods.contains(&Method::OPTIONS) {
+                    allowed_methods.push(Method::OPTIONS);
+                }
+                if head.method() == Method::OPTIONS {
+                    options(&allowed_methods)
+                } else {
+                    method_not_allowed(&allowed_methods)
+                }
+            }
+        };
+        // Only sessions which a route has loaded can have been modified.
+        let loaded = session.as_ref().and_then(SessionLoader::loaded);
+        let resp = match (&self.session, &loaded) {
+            (Some(config), Some(session)) => {
+                let mut resp = resp;
+                match config.save(session, &mut resp).await {
+                    Ok(()) => resp,
+                    Err(e) => NailsError::from(e).to_response(),
+                }
+            }
+            _ => resp,
         };
         let resp = {
             let mut resp = resp;
@@ -212,4 +425,780 @@ where
         };
         Ok(resp)
     }
+
+    /// Returns the canonical path if it differs from the requested one.
+    ///
+    /// `.`, `..` and empty segments are always removed so that they never reach routes.
+    /// The trailing slash is toggled only when it leads to a route.
+    fn canonical_path(&self, head: &RequestHead<'_>) -> Option<String> {
+        if self.path_normalization == PathNormalization::Strict {
+            return None;
+        }
+        let path = head.path();
+        let normalized = normalize_path(path);
+        let has_route = |candidate: &str| {
+            !self
+                .router
+                .allowed_methods(&head.with_path(candidate))
+                .is_empty()
+        };
+        if !has_route(&normalized) {
+            if let Some(toggled) = toggle_trailing_slash(&normalized) {
+                if has_route(&toggled) {
+                    return Some(toggled);
+                }
+            }
+        }
+        if normalized != path {
+            Some(normalized)
+        } else {
+            None
+        }
+    }
+}
+
+/// Removes empty, `.` and `..` segments. `..` never goes above the root.
+fn normalize_path(path: &str) -> String {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let trailing_slash = path.ends_with('/') || path.ends_with("/.") || path.ends_with("/..");
+    let mut normalized = String::with_capacity(path.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing_slash || segments.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+fn toggle_trailing_slash(path: &str) -> Option<String> {
+    if path == "/" {
+        None
+    } else if let Some(path) = path.strip_suffix('/') {
+        Some(path.to_owned())
+    } else {
+        Some(format!("{}/", path))
+    }
+}
+
+fn replace_path(req: Request<Body>, path: &str) -> Request<Body> {
+    let (mut parts, body) = req.into_parts();
+    let path_and_query = match parts.uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    };
+    let mut uri_parts = parts.uri.into_parts();
+    uri_parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .expect("normalized path should be a valid URI"),
+    );
+    parts.uri = Uri::from_parts(uri_parts).expect("normalized path should be a valid URI");
+    Request::from_parts(parts, body)
+}
+
+fn strip_body(resp: Response<Body>, fill_content_length: bool) -> Response<Body> {
+    let (mut parts, body) = resp.into_parts();
+    if fill_content_length && !parts.headers.contains_key("Content-Length") {
+        if let Some(len) = body.size_hint().exact() {
+            parts.headers.insert("Content-Length", len.into());
+        }
+    }
+    Response::from_parts(parts, Body::empty())
+}
+
+fn permanent_redirect(location: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::PERMANENT_REDIRECT)
+        .header("Location", location)
+        .body(Body::from("Permanent Redirect"))
+        .unwrap()
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from("Not Found"))
+        .unwrap()
+}
+
+fn method_not_allowed(allowed_methods: &[Method]) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::METHOD_NOT_ALLOWED)
+        .header("Allow", allow_header(allowed_methods))
+        .body(Body::from("Method Not Allowed"))
+        .unwrap()
+}
+
+fn options(allowed_methods: &[Method]) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Allow", allow_header(allowed_methods))
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn allow_header(methods: &[Method]) -> String {
+    methods
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    use crate::session::{CookieProtection, MemoryStore, Session, SessionData};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users", method = "POST")]
+    struct CreateUserRequest;
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/{id}")]
+    struct GetUserRequest {
+        #[allow(dead_code)]
+        id: u64,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/posts", method = "GET_ONLY")]
+    struct ListPostsRequest;
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/posts", method = "HEAD")]
+    struct HeadPostsRequest;
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/{id}", method = "OPTIONS")]
+    struct UserOptionsRequest {
+        #[allow(dead_code)]
+        id: u64,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/feed", method = ["GET", "HEAD"])]
+    struct FeedRequest;
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/me")]
+    struct GetMeRequest;
+
+    async fn respond_text<Req>(_ctx: (), _req: Req) -> Result<Response<Body>, NailsError> {
+        Ok(Response::new(Body::from("text")))
+    }
+
+    fn service() -> Service<()> {
+        Service::builder()
+            .add_function_route(respond_text::<CreateUserRequest>)
+            .add_function_route(respond_text::<GetUserRequest>)
+            .finish()
+    }
+
+    fn request(service: &Service<()>, method: Method, path: &str) -> Response<Body> {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        block_on(service.inner.respond(&(), req)).unwrap()
+    }
+
+    fn body_string(resp: Response<Body>) -> String {
+        block_on(async {
+            let mut body = resp.into_body();
+            let mut buf = Vec::new();
+            while let Some(chunk) = body.next().await {
+                buf.extend_from_slice(chunk.unwrap().as_ref());
+            }
+            String::from_utf8(buf).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_not_found() {
+        let service = service();
+        let resp = request(&service, Method::GET, "/api/tags");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(resp.headers().get("Allow").is_none());
+    }
+
+    #[test]
+    fn test_typed_overlap() {
+        async fn respond_me(_ctx: (), _req: GetMeRequest) -> Result<Response<Body>, NailsError> {
+            Ok(Response::new(Body::from("me")))
+        }
+
+        let service = Service::builder()
+            .add_function_route(respond_text::<GetUserRequest>)
+            .add_function_route(respond_me)
+            .finish();
+        let resp = request(&service, Method::GET, "/api/users/me");
+        assert_eq!(body_string(resp), "me");
+        let resp = request(&service, Method::GET, "/api/users/42");
+        assert_eq!(body_string(resp), "text");
+    }
+
+    #[test]
+    fn test_method_not_allowed() {
+        let service = service();
+        let resp = request(&service, Method::GET, "/api/users");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()["Allow"], "POST, OPTIONS");
+
+        let resp = request(&service, Method::DELETE, "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()["Allow"], "GET, HEAD, OPTIONS");
+    }
+
+    #[test]
+    fn test_options() {
+        let service = service();
+        let resp = request(&service, Method::OPTIONS, "/api/users");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Allow"], "POST, OPTIONS");
+
+        let resp = request(&service, Method::OPTIONS, "/api/tags");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_head() {
+        let service = service();
+        let resp = request(&service, Method::HEAD, "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Length"], "4");
+        assert!(resp.body().is_end_stream());
+
+        let resp = request(&service, Method::HEAD, "/api/users");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn test_explicit_head() {
+        let service = Service::builder()
+            .add_function_route(respond_text::<ListPostsRequest>)
+            .add_function_route(|_ctx: (), _req: HeadPostsRequest| async {
+                Ok(Response::builder()
+                    .header("X-Head", "explicit")
+                    .body(Body::empty())
+                    .unwrap())
+            })
+            .finish();
+        let resp = request(&service, Method::HEAD, "/api/posts");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["X-Head"], "explicit");
+        assert!(resp.headers().get("Content-Length").is_none());
+
+        let resp = request(&service, Method::GET, "/api/posts");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("X-Head").is_none());
+    }
+
+    #[test]
+    fn test_explicit_get_and_head() {
+        let service = Service::builder()
+            .add_function_route(respond_text::<FeedRequest>)
+            .finish();
+        let resp = request(&service, Method::HEAD, "/api/feed");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Content-Length").is_none());
+        assert!(resp.body().is_end_stream());
+
+        let resp = request(&service, Method::GET, "/api/feed");
+        assert_eq!(body_string(resp), "text");
+    }
+
+    #[test]
+    fn test_head_in_mounted_router() {
+        let mut api = Router::new();
+        api.add_function_route(respond_text::<GetUserRequest>);
+        api.add_function_route(respond_text::<ListPostsRequest>);
+        api.add_function_route(|_ctx: (), _req: HeadPostsRequest| async {
+            Ok(Response::builder()
+                .header("X-Head", "explicit")
+                .body(Body::empty())
+                .unwrap())
+        });
+        let service = Service::builder().mount("/v1", api).finish();
+        let resp = request(&service, Method::HEAD, "/v1/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Length"], "4");
+        assert!(resp.body().is_end_stream());
+
+        let resp = request(&service, Method::HEAD, "/v1/api/posts");
+        assert_eq!(resp.headers()["X-Head"], "explicit");
+        assert!(resp.headers().get("Content-Length").is_none());
+    }
+
+    #[derive(Debug, Clone)]
+    struct AppCtx;
+
+    impl Context for AppCtx {}
+
+    impl AsContext<()> for AppCtx {
+        fn as_context(&self) -> std::borrow::Cow<'_, ()> {
+            std::borrow::Cow::Owned(())
+        }
+    }
+
+    #[test]
+    fn test_mount_service() {
+        let admin = service();
+        let service = Service::<AppCtx>::builder()
+            .mount_service("/admin", &admin)
+            .finish();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/admin/api/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let resp = block_on(service.inner.respond(&AppCtx, req)).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/admin/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let resp = block_on(service.inner.respond(&AppCtx, req)).unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn test_subcontext_function_route() {
+        let service = Service::<AppCtx>::builder()
+            .add_subcontext_function_route(respond_text::<GetUserRequest>)
+            .finish();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/api/users/42")
+            .body(Body::empty())
+            .unwrap();
+        let resp = block_on(service.inner.respond(&AppCtx, req)).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_explicit_options() {
+        let service = Service::builder()
+            .add_function_route(respond_text::<GetUserRequest>)
+            .add_function_route(|_ctx: (), _req: UserOptionsRequest| async {
+                Ok(Response::new(Body::from("explicit")))
+            })
+            .finish();
+        let resp = request(&service, Method::OPTIONS, "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Allow").is_none());
+
+        let resp = request(&service, Method::DELETE, "/api/users/42");
+        assert_eq!(resp.headers()["Allow"], "GET, HEAD, OPTIONS");
+    }
+
+    #[test]
+    fn test_routes() {
+        let admin = service();
+        let service = Service::<AppCtx>::builder()
+            .add_subcontext_function_route(respond_text::<ListPostsRequest>)
+            .mount_service("/admin", &admin)
+            .finish();
+        let routes = service.routes();
+        assert_eq!(
+            routes
+                .iter()
+                .map(|route| (route.methods.clone(), route.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (vec![Method::GET], "/api/posts"),
+                (vec![Method::POST], "/admin/api/users"),
+                (vec![Method::GET, Method::HEAD], "/admin/api/users/{id}"),
+            ],
+        );
+        assert!(routes[1].request_type.ends_with("::CreateUserRequest"));
+        assert!(routes[1]
+            .handler
+            .unwrap()
+            .contains("respond_text<nails::service::tests::CreateUserRequest>"));
+        assert!(format!("{:?}", service).contains("/admin/api/users/{id}"));
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/posts/")]
+    struct ListPostsSlashRequest;
+
+    fn normalizing_service(policy: PathNormalization) -> Service<()> {
+        Service::builder()
+            .path_normalization(policy)
+            .add_function_route(respond_text::<CreateUserRequest>)
+            .add_function_route(respond_text::<GetUserRequest>)
+            .add_function_route(respond_text::<ListPostsSlashRequest>)
+            .finish()
+    }
+
+    #[test]
+    fn test_path_normalization_strict() {
+        let service = normalizing_service(PathNormalization::Strict);
+        let resp = request(&service, Method::GET, "/api/users/42/");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = request(&service, Method::GET, "/api//users/42");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = request(&service, Method::GET, "/api/posts/");
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_path_normalization_redirect() {
+        let service = normalizing_service(PathNormalization::Redirect);
+        let resp = request(&service, Method::GET, "/api/users/42/");
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["Location"], "/api/users/42");
+
+        let resp = request(&service, Method::GET, "//api/./posts/../users/42?q=1");
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["Location"], "/api/users/42?q=1");
+
+        let resp = request(&service, Method::GET, "/api/posts");
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["Location"], "/api/posts/");
+
+        let resp = request(&service, Method::GET, "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(&service, Method::GET, "/api/tags/");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_path_normalization_normalize() {
+        let service = normalizing_service(PathNormalization::Normalize);
+        let resp = request(&service, Method::GET, "/api/users/42/");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(&service, Method::GET, "/api/users//42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(&service, Method::DELETE, "/api/users/42/");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()["Allow"], "GET, HEAD, OPTIONS");
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/static/{*rest}")]
+    struct StaticRequest {
+        rest: String,
+    }
+
+    #[test]
+    fn test_path_normalization_catch_all() {
+        let service = Service::builder()
+            .path_normalization(PathNormalization::Normalize)
+            .add_function_route(|_ctx: (), req: StaticRequest| async move {
+                Ok(Response::new(Body::from(req.rest)))
+            })
+            .finish();
+        let resp = request(&service, Method::GET, "/static/../secret");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = request(&service, Method::GET, "/static/css/../js//app.js");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_string(resp), "js/app.js");
+
+        let service = Service::builder()
+            .path_normalization(PathNormalization::Redirect)
+            .add_function_route(|_ctx: (), req: StaticRequest| async move {
+                Ok(Response::new(Body::from(req.rest)))
+            })
+            .finish();
+        let resp = request(&service, Method::GET, "/static/../secret");
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["Location"], "/secret");
+        let resp = request(&service, Method::GET, "/static/./app.js");
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["Location"], "/static/app.js");
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/"), "/");
+        assert_eq!(normalize_path("//"), "/");
+        assert_eq!(normalize_path("/api//users/"), "/api/users/");
+        assert_eq!(normalize_path("/api/./users/../posts"), "/api/posts");
+        assert_eq!(normalize_path("/api/users/.."), "/api/");
+        assert_eq!(normalize_path("/../../api"), "/api");
+        assert_eq!(normalize_path("/api/..."), "/api/...");
+        assert_eq!(toggle_trailing_slash("/"), None);
+        assert_eq!(toggle_trailing_slash("/api/"), Some("/api".to_owned()));
+        assert_eq!(toggle_trailing_slash("/api"), Some("/api/".to_owned()));
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/api/users/{id}", method = ["PUT", "PATCH"])]
+    struct UpdateUserRequest {
+        #[allow(dead_code)]
+        id: u64,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/dav/{name}", method = "PROPFIND")]
+    struct PropfindRequest {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[test]
+    fn test_multiple_and_extension_methods() {
+        let service = Service::builder()
+            .add_function_route(respond_text::<GetUserRequest>)
+            .add_function_route(respond_text::<UpdateUserRequest>)
+            .add_function_route(respond_text::<PropfindRequest>)
+            .finish();
+        let resp = request(&service, Method::PUT, "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(&service, Method::PATCH, "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(&service, Method::DELETE, "/api/users/42");
+        assert_eq!(resp.headers()["Allow"], "GET, HEAD, PUT, PATCH, OPTIONS");
+
+        let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+        let resp = request(&service, propfind, "/dav/file.txt");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(&service, Method::GET, "/dav/file.txt");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()["Allow"], "PROPFIND, OPTIONS");
+    }
+
+    #[test]
+    fn test_mount_host() {
+        let mut admin = Router::new();
+        admin.add_function_route(respond_text::<CreateUserRequest>);
+        let service = Service::builder()
+            .add_function_route(respond_text::<GetUserRequest>)
+            .mount_host("admin.example.com", admin)
+            .finish();
+        let request = |method: Method, host: &str, path: &str| {
+            let req = Request::builder()
+                .method(method)
+                .uri(path)
+                .header("Host", host)
+                .body(Body::empty())
+                .unwrap();
+            block_on(service.inner.respond(&(), req)).unwrap()
+        };
+        let resp = request(Method::POST, "admin.example.com", "/api/users");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request(Method::POST, "www.example.com", "/api/users");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = request(Method::GET, "admin.example.com", "/api/users");
+        assert_eq!(resp.headers()["Allow"], "POST, OPTIONS");
+        let resp = request(Method::GET, "www.example.com", "/api/users/42");
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_fallback() {
+        let service = Service::builder()
+            .add_function_route(respond_text::<CreateUserRequest>)
+            .fallback(|_ctx: (), req: Request<Body>| async move {
+                if req.uri().path().starts_with("/api/") {
+                    return Err(crate::error::ContentTypeError {
+                        expected: vec!["application/json".to_owned()],
+                        got: None,
+                    }
+                    .into());
+                }
+                Ok(Response::builder()
+                    .header("Content-Type", "text/html")
+                    .body(Body::from("<!doctype html>"))
+                    .unwrap())
+            })
+            .finish();
+        let resp = request(&service, Method::GET, "/articles/foo");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Type"], "text/html");
+        assert_eq!(resp.headers()["Access-Control-Allow-Origin"], "*");
+
+        let resp = request(&service, Method::HEAD, "/articles/foo");
+        assert_eq!(resp.headers()["Content-Length"], "15");
+        assert!(resp.body().is_end_stream());
+
+        let resp = request(&service, Method::GET, "/api/tags");
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+
+        let resp = request(&service, Method::GET, "/api/users");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/login", method = "POST")]
+    struct LoginRequest {
+        #[nails(session)]
+        session: Session,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/me")]
+    struct MeRequest {
+        #[nails(session)]
+        session: Session,
+    }
+
+    #[derive(Debug, Preroute)]
+    #[nails(path = "/logout", method = "POST")]
+    struct LogoutRequest {
+        #[nails(session)]
+        session: Session,
+    }
+
+    async fn login(_ctx: (), req: LoginRequest) -> Result<Response<Body>, NailsError> {
+        req.session.insert("user", "alice").unwrap();
+        req.session.renew();
+        Ok(Response::new(Body::empty()))
+    }
+
+    async fn me(_ctx: (), req: MeRequest) -> Result<Response<Body>, NailsError> {
+        let user = req.session.get::<String>("user").unwrap_or_default();
+        Ok(Response::builder()
+            .header("X-User", user)
+            .body(Body::empty())
+            .unwrap())
+    }
+
+    async fn logout(_ctx: (), req: LogoutRequest) -> Result<Response<Body>, NailsError> {
+        req.session.destroy();
+        Ok(Response::new(Body::empty()))
+    }
+
+    #[test]
+    fn test_session() {
+        for &protection in &[CookieProtection::Signed, CookieProtection::Encrypted] {
+            let mut builder = Service::builder();
+            builder
+                .session(SessionKey::generate(), MemoryStore::new())
+                .protection(protection);
+            let service = builder
+                .add_function_route(login)
+                .add_function_route(me)
+                .add_function_route(logout)
+                .finish();
+            let request = |method: Method, path: &str, cookie: &str| {
+                let req = Request::builder()
+                    .method(method)
+                    .uri(path)
+                    .header("Cookie", cookie)
+                    .body(Body::empty())
+                    .unwrap();
+                block_on(service.inner.respond(&(), req)).unwrap()
+            };
+
+            let resp = request(Method::GET, "/me", "");
+            assert_eq!(resp.headers()["X-User"], "");
+            assert!(resp.headers().get("Set-Cookie").is_none());
+
+            let resp = request(Method::POST, "/login", "");
+            let set_cookie = resp.headers()["Set-Cookie"].to_str().unwrap();
+            assert!(set_cookie.starts_with("session="));
+            assert!(set_cookie.ends_with("; Path=/; SameSite=Lax; HttpOnly"));
+            let cookie = set_cookie.split(';').next().unwrap().to_owned();
+
+            let resp = request(Method::GET, "/me", &cookie);
+            assert_eq!(resp.headers()["X-User"], "alice");
+            assert!(resp.headers().get("Set-Cookie").is_none());
+            let tampered = format!("{}x", cookie);
+            let resp = request(Method::GET, "/me", &tampered);
+            assert_eq!(resp.headers()["X-User"], "");
+
+            let resp = request(Method::POST, "/login", &cookie);
+            let renewed = resp.headers()["Set-Cookie"].to_str().unwrap();
+            assert!(!renewed.starts_with(&cookie));
+            let resp = request(Method::GET, "/me", &cookie);
+            assert_eq!(resp.headers()["X-User"], "");
+            let cookie = renewed.split(';').next().unwrap().to_owned();
+
+            let resp = request(Method::POST, "/logout", &cookie);
+            assert_eq!(resp.headers()["Set-Cookie"], "session=; Path=/; Max-Age=0");
+            let resp = request(Method::GET, "/me", &cookie);
+            assert_eq!(resp.headers()["X-User"], "");
+        }
+    }
+
+    /// A store whose `load` fails while `down` is set.
+    #[derive(Default)]
+    struct FlakyStore {
+        sessions: MemoryStore,
+        down: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl SessionStore for FlakyStore {
+        async fn load(&self, id: &str) -> Result<Option<SessionData>, failure::Error> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(failure::err_msg("store is down"));
+            }
+            self.sessions.load(id).await
+        }
+        async fn save(&self, id: &str, data: &SessionData) -> Result<(), failure::Error> {
+            self.sessions.save(id, data).await
+        }
+        async fn remove(&self, id: &str) -> Result<(), failure::Error> {
+            self.sessions.remove(id).await
+        }
+    }
+
+    #[test]
+    fn test_session_store_down() {
+        let store = FlakyStore::default();
+        let down = store.down.clone();
+        let mut builder = Service::builder();
+        builder.session(SessionKey::generate(), store);
+        let service = builder
+            .add_function_route(login)
+            .add_function_route(me)
+            .add_function_route(respond_text::<GetUserRequest>)
+            .finish();
+        let request = |method: Method, path: &str, cookie: &str| {
+            let req = Request::builder()
+                .method(method)
+                .uri(path)
+                .header("Cookie", cookie)
+                .body(Body::empty())
+                .unwrap();
+            block_on(service.inner.respond(&(), req)).unwrap()
+        };
+        let resp = request(Method::POST, "/login", "");
+        let set_cookie = resp.headers()["Set-Cookie"].to_str().unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_owned();
+
+        down.store(true, Ordering::SeqCst);
+        let resp = request(Method::GET, "/api/users/42", &cookie);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_string(resp), "text");
+        let resp = request(Method::GET, "/nowhere", &cookie);
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = request(Method::GET, "/me", &cookie);
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+        assert!(resp.headers().get("X-User").is_none());
+
+        down.store(false, Ordering::SeqCst);
+        let resp = request(Method::GET, "/me", &cookie);
+        assert_eq!(resp.headers()["X-User"], "alice");
+    }
+
+    #[test]
+    fn test_session_not_configured() {
+        let service = Service::builder().add_function_route(me).finish();
+        let resp = request(&service, Method::GET, "/me");
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+    }
 }
@@ -0,0 +1,589 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hyper::{Body, HeaderMap, Request, Response};
+use ring::aead::{self, Aad, Nonce, OpeningKey, SealingKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::cookie::{CookieJar, SameSite};
+use crate::error::SessionError;
+use crate::request::parse_cookies;
+
+/// Values stored in a session.
+pub type SessionData = serde_json::Map<String, serde_json::Value>;
+
+/// Where session data is kept, keyed by session ID.
+#[async_trait]
+pub trait SessionStore: Send + Sync + 'static {
+    async fn load(&self, id: &str) -> Result<Option<SessionData>, failure::Error>;
+    async fn save(&self, id: &str, data: &SessionData) -> Result<(), failure::Error>;
+    async fn remove(&self, id: &str) -> Result<(), failure::Error>;
+}
+
+/// A store keeping sessions in process memory. Sessions never expire.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, SessionData>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionData>, failure::Error> {
+        Ok(self.sessions.lock().unwrap().get(id).cloned())
+    }
+
+    async fn save(&self, id: &str, data: &SessionData) -> Result<(), failure::Error> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(id.to_owned(), data.clone());
+        Ok(())
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), failure::Error> {
+        self.sessions.lock().unwrap().remove(id);
+        Ok(())
+    }
+}
+
+const SIGNING_CONTEXT: &[u8] = b"nails session cookie signing";
+const ENCRYPTION_CONTEXT: &[u8] = b"nails session cookie encryption";
+
+/// The secret protecting session cookies.
+///
+/// Separate keys for signing and encryption are derived from it.
+#[derive(Clone)]
+pub struct SessionKey {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl SessionKey {
+    /// Creates a key from a secret, which should come from configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `secret` is shorter than 32 bytes.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        assert!(
+            secret.len() >= 32,
+            "session key must be at least 32 bytes long"
+        );
+        let master = hmac::SigningKey::new(&digest::SHA256, secret);
+        let derive = |context: &[u8]| {
+            let mut key = [0; 32];
+            key.copy_from_slice(hmac::sign(&master, context).as_ref());
+            key
+        };
+        Self {
+            signing: derive(SIGNING_CONTEXT),
+            encryption: derive(ENCRYPTION_CONTEXT),
+        }
+    }
+
+    /// Creates a random key. Sessions don't survive restarts with it.
+    pub fn generate() -> Self {
+        let mut secret = [0; 32];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("failed to generate a session key");
+        Self::from_secret(&secret)
+    }
+}
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SessionKey").finish()
+    }
+}
+
+/// How the session ID is protected in the cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CookieProtection {
+    /// The ID is sent in the clear along with an HMAC-SHA256 tag.
+    #[default]
+    Signed,
+    /// The ID is encrypted with AES-256-GCM, hiding it from the client.
+    Encrypted,
+}
+
+/// Session settings of a service, created by
+/// [`Builder::session`](crate::service::Builder::session).
+pub struct SessionConfig {
+    key: SessionKey,
+    store: Arc<dyn SessionStore>,
+    protection: CookieProtection,
+    cookie_name: String,
+    path: String,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    same_site: SameSite,
+    secure: bool,
+}
+
+impl SessionConfig {
+    pub(crate) fn new(key: SessionKey, store: Arc<dyn SessionStore>) -> Self {
+        Self {
+            key,
+            store,
+            protection: CookieProtection::default(),
+            cookie_name: "session".to_owned(),
+            path: "/".to_owned(),
+            domain: None,
+            max_age: None,
+            same_site: SameSite::Lax,
+            secure: false,
+        }
+    }
+
+    pub fn protection(&mut self, protection: CookieProtection) -> &mut Self {
+        self.protection = protection;
+        self
+    }
+
+    /// Sets the cookie name. Defaults to `session`.
+    pub fn cookie_name(&mut self, name: &str) -> &mut Self {
+        self.cookie_name = name.to_owned();
+        self
+    }
+
+    /// Sets the cookie path. Defaults to `/`.
+    pub fn path(&mut self, path: &str) -> &mut Self {
+        self.path = path.to_owned();
+        self
+    }
+
+    pub fn domain(&mut self, domain: &str) -> &mut Self {
+        self.domain = Some(domain.to_owned());
+        self
+    }
+
+    /// Makes the cookie persistent. By default it lasts until the browser is closed.
+    pub fn max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the `SameSite` attribute. Defaults to `Lax`.
+    pub fn same_site(&mut self, same_site: SameSite) -> &mut Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Restricts the cookie to HTTPS. Should be enabled in production.
+    pub fn secure(&mut self, secure: bool) -> &mut Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Prepares to load the session the request's cookie refers to. The store isn't accessed yet.
+    pub(crate) fn loader(&self, req: &Request<Body>) -> SessionLoader {
+        SessionLoader {
+            store: self.store.clone(),
+            id: self.session_id(req.headers()),
+            session: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Writes back a modified session and sets the cookie accordingly.
+    pub(crate) async fn save(
+        &self,
+        session: &Session,
+        resp: &mut Response<Body>,
+    ) -> Result<(), SessionError> {
+        let (old_id, new_id, data) = {
+            let state = session.state();
+            if !state.modified {
+                return Ok(());
+            }
+            let new_id = if state.destroyed || (state.data.is_empty() && state.id.is_none()) {
+                None
+            } else if state.renewed || state.id.is_none() {
+                Some(generate_id())
+            } else {
+                state.id.clone()
+            };
+            (state.id.clone(), new_id, state.data.clone())
+        };
+        let mut jar = CookieJar::new();
+        if let Some(new_id) = &new_id {
+            self.store
+                .save(new_id, &data)
+                .await
+                .map_err(SessionError::StoreError)?;
+            let cookie = jar.add(&self.cookie_name, &self.protect(new_id));
+            cookie
+                .path(&self.path)
+                .same_site(self.same_site)
+                .secure(self.secure)
+                .http_only(true);
+            if let Some(domain) = &self.domain {
+                cookie.domain(domain);
+            }
+            if let Some(max_age) = self.max_age {
+                cookie.max_age(max_age);
+            }
+        }
+        if let Some(old_id) = &old_id {
+            if new_id.as_ref() != Some(old_id) {
+                self.store
+                    .remove(old_id)
+                    .await
+                    .map_err(SessionError::StoreError)?;
+            }
+            if new_id.is_none() {
+                let cookie = jar.remove(&self.cookie_name);
+                cookie.path(&self.path);
+                if let Some(domain) = &self.domain {
+                    cookie.domain(domain);
+                }
+            }
+        }
+        jar.apply(resp.headers_mut());
+        Ok(())
+    }
+
+    fn session_id(&self, headers: &HeaderMap) -> Option<String> {
+        parse_cookies(headers)
+            .get(&self.cookie_name)?
+            .iter()
+            .find_map(|value| self.unprotect(value))
+    }
+
+    fn protect(&self, id: &str) -> String {
+        match self.protection {
+            CookieProtection::Signed => {
+                let tag = hmac::sign(&self.signing_key(), &self.signed_content(id));
+                format!("{}.{}", id, encode(tag.as_ref()))
+            }
+            CookieProtection::Encrypted => {
+                let key = SealingKey::new(&AES_256_GCM, &self.key.encryption)
+                    .expect("internal error: invalid encryption key");
+                let mut nonce = [0; NONCE_LEN];
+                SystemRandom::new()
+                    .fill(&mut nonce)
+                    .expect("failed to generate a nonce");
+                let mut in_out = id.as_bytes().to_vec();
+                in_out.resize(in_out.len() + AES_256_GCM.tag_len(), 0);
+                aead::seal_in_place(
+                    &key,
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::from(self.cookie_name.as_bytes()),
+                    &mut in_out,
+                    AES_256_GCM.tag_len(),
+                )
+                .expect("failed to encrypt session cookie");
+                let mut value = nonce.to_vec();
+                value.extend_from_slice(&in_out);
+                encode(&value)
+            }
+        }
+    }
+
+    fn unprotect(&self, value: &str) -> Option<String> {
+        match self.protection {
+            CookieProtection::Signed => {
+                let dot = value.find('.')?;
+                let (id, tag) = (&value[..dot], decode(&value[dot + 1..])?);
+                hmac::verify_with_own_key(&self.signing_key(), &self.signed_content(id), &tag)
+                    .ok()?;
+                Some(id.to_owned())
+            }
+            CookieProtection::Encrypted => {
+                let mut value = decode(value)?;
+                if value.len() < NONCE_LEN {
+                    return None;
+                }
+                let mut nonce = [0; NONCE_LEN];
+                nonce.copy_from_slice(&value[..NONCE_LEN]);
+                let key = OpeningKey::new(&AES_256_GCM, &self.key.encryption)
+                    .expect("internal error: invalid encryption key");
+                let id = aead::open_in_place(
+                    &key,
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::from(self.cookie_name.as_bytes()),
+                    NONCE_LEN,
+                    &mut value,
+                )
+                .ok()?;
+                String::from_utf8(id.to_vec()).ok()
+            }
+        }
+    }
+
+    fn signing_key(&self) -> hmac::SigningKey {
+        hmac::SigningKey::new(&digest::SHA256, &self.key.signing)
+    }
+
+    // The tag covers the cookie name so that it can't be replayed under another name.
+    fn signed_content(&self, id: &str) -> Vec<u8> {
+        format!("{}={}", self.cookie_name, id).into_bytes()
+    }
+}
+
+impl fmt::Debug for SessionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SessionConfig")
+            .field("protection", &self.protection)
+            .field("cookie_name", &self.cookie_name)
+            .field("path", &self.path)
+            .field("domain", &self.domain)
+            .field("max_age", &self.max_age)
+            .field("same_site", &self.same_site)
+            .field("secure", &self.secure)
+            .finish()
+    }
+}
+
+fn generate_id() -> String {
+    let mut id = [0; 32];
+    SystemRandom::new()
+        .fill(&mut id)
+        .expect("failed to generate a session ID");
+    encode(&id)
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(s: &str) -> Option<Vec<u8>> {
+    base64::decode_config(s, base64::URL_SAFE_NO_PAD).ok()
+}
+
+/// Loads the session of the current request from the store on first use.
+///
+/// The service puts it in the request extensions when sessions are enabled,
+/// so that requests not using the session never access the store.
+#[derive(Clone)]
+pub struct SessionLoader {
+    store: Arc<dyn SessionStore>,
+    id: Option<String>,
+    session: Arc<Mutex<Option<Session>>>,
+}
+
+impl SessionLoader {
+    /// Returns the session, or starts an empty one if the cookie doesn't refer to a stored session.
+    pub async fn load(&self) -> Result<Session, SessionError> {
+        if let Some(session) = self.loaded() {
+            return Ok(session);
+        }
+        let data = if let Some(id) = &self.id {
+            self.store
+                .load(id)
+                .await
+                .map_err(SessionError::StoreError)?
+        } else {
+            None
+        };
+        let session = if let Some(data) = data {
+            Session::new(self.id.clone(), data)
+        } else {
+            Session::new(None, SessionData::new())
+        };
+        // Another clone may have loaded it in the meantime.
+        Ok(self.session.lock().unwrap().get_or_insert(session).clone())
+    }
+
+    /// Returns the session if it has been loaded.
+    pub(crate) fn loaded(&self) -> Option<Session> {
+        self.session.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for SessionLoader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SessionLoader")
+            .field("session", &self.loaded())
+            .finish()
+    }
+}
+
+/// The session of the current request.
+///
+/// Clones share the same session. Changes are saved after the handler returns,
+/// and the cookie is set if needed.
+#[derive(Debug, Clone)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+#[derive(Debug)]
+struct SessionState {
+    id: Option<String>,
+    data: SessionData,
+    modified: bool,
+    renewed: bool,
+    destroyed: bool,
+}
+
+impl Session {
+    fn new(id: Option<String>, data: SessionData) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SessionState {
+                id,
+                data,
+                modified: false,
+                renewed: false,
+                destroyed: false,
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Returns the value for `key`, or `None` if it's absent or not a `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.state().data.get(key)?.clone();
+        serde_json::from_value(value).ok()
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.state();
+        state.data.insert(key.to_owned(), value);
+        state.destroyed = false;
+        state.modified = true;
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut state = self.state();
+        if state.data.remove(key).is_some() {
+            state.modified = true;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().data.is_empty()
+    }
+
+    /// Changes the session ID while keeping the data.
+    ///
+    /// Call this when the privilege changes, e.g. on login, to prevent session fixation.
+    pub fn renew(&self) {
+        let mut state = self.state();
+        state.renewed = true;
+        state.modified = true;
+    }
+
+    /// Removes the session from the store and the client.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.destroyed = true;
+        state.modified = true;
+    }
+}
+
+/// Extracts the session in `#[nails(session)]` fields.
+///
+/// `loader` is `None` if sessions aren't enabled for the service.
+#[async_trait]
+pub trait FromSession: Sized {
+    async fn from_session(loader: Option<&SessionLoader>) -> Result<Self, SessionError>;
+}
+
+#[async_trait]
+impl FromSession for Session {
+    async fn from_session(loader: Option<&SessionLoader>) -> Result<Self, SessionError> {
+        loader.ok_or(SessionError::NotConfigured)?.load().await
+    }
+}
+
+#[async_trait]
+impl FromSession for Option<Session> {
+    async fn from_session(loader: Option<&SessionLoader>) -> Result<Self, SessionError> {
+        if let Some(loader) = loader {
+            Ok(Some(loader.load().await?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    fn config(secret: u8, protection: CookieProtection) -> SessionConfig {
+        let mut config = SessionConfig::new(
+            SessionKey::from_secret(&[secret; 32]),
+            Arc::new(MemoryStore::new()),
+        );
+        config.protection(protection);
+        config
+    }
+
+    #[test]
+    fn test_protect_signed() {
+        let config = config(42, CookieProtection::Signed);
+        let value = config.protect("abc");
+        assert!(value.starts_with("abc."));
+        assert_eq!(config.unprotect(&value), Some("abc".to_owned()));
+        assert_eq!(config.unprotect(&format!("abd.{}", &value[4..])), None);
+        assert_eq!(config.unprotect("abc"), None);
+
+        let mut other = self::config(42, CookieProtection::Signed);
+        other.cookie_name("other");
+        assert_eq!(other.unprotect(&value), None);
+        let other = self::config(43, CookieProtection::Signed);
+        assert_eq!(other.unprotect(&value), None);
+    }
+
+    #[test]
+    fn test_protect_encrypted() {
+        let config = config(42, CookieProtection::Encrypted);
+        let value = config.protect("abc");
+        assert!(!value.contains("abc"));
+        assert_ne!(config.protect("abc"), value);
+        assert_eq!(config.unprotect(&value), Some("abc".to_owned()));
+        assert_eq!(config.unprotect(&value[1..]), None);
+        assert_eq!(config.unprotect(""), None);
+
+        let other = self::config(43, CookieProtection::Encrypted);
+        assert_eq!(other.unprotect(&value), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "session key must be at least 32 bytes long")]
+    fn test_short_session_key() {
+        SessionKey::from_secret(b"secret");
+    }
+
+    #[test]
+    fn test_session() {
+        let session = Session::new(None, SessionData::new());
+        assert!(session.is_empty());
+        session.insert("user_id", 42).unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(42));
+        assert_eq!(session.get::<String>("user_id"), None);
+        assert_eq!(session.get::<u32>("missing"), None);
+        session.clone().remove("user_id");
+        assert!(session.is_empty());
+        assert!(session.state().modified);
+    }
+}
@@ -1,6 +1,10 @@
-use proc_macro2::Span;
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
-use syn::{Attribute, Lit, LitStr, Meta, NestedMeta};
+use syn::{token, Attribute, Expr, ExprLit, Ident, Lit, LitStr, Meta, NestedMeta, Token};
 
 #[cfg(feature = "proc_macro_diagnostics")]
 macro_rules! if_proc_macro_diagnostics {
@@ -14,48 +18,71 @@ macro_rules! if_proc_macro_diagnostics {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct StructAttrs {
     pub(crate) path: Option<PathInfo>,
+    pub(crate) host: Option<HostInfo>,
     pub(crate) method: Option<MethodInfo>,
+    pub(crate) to_uri: Option<ToUriInfo>,
 }
 
 impl StructAttrs {
     pub(crate) fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut ret = Self {
             path: None,
+            host: None,
             method: None,
+            to_uri: None,
         };
         for attr in attrs {
             if !attr.path.is_ident("nails") {
                 continue;
             }
-            let meta = attr.parse_meta()?;
-            let list = match meta {
-                Meta::Path(path) => {
-                    return Err(syn::Error::new(
-                        path.span(),
-                        "#[nails] must have an argument list",
-                    ));
-                }
-                Meta::NameValue(nv) => {
+            // Parsed by hand rather than with `parse_meta` as `method = [...]` isn't a valid meta.
+            let parser = |input: ParseStream<'_>| {
+                let content;
+                let paren_token = syn::parenthesized!(content in input);
+                let args = Punctuated::<StructArg, Token![,]>::parse_terminated(&content)?;
+                Ok((paren_token, args))
+            };
+            let (_paren_token, args) = match parser.parse2(attr.tokens.clone()) {
+                Ok(args) => args,
+                Err(_) if !is_parenthesized(&attr.tokens) => {
                     return Err(syn::Error::new(
-                        nv.span(),
+                        attr.span(),
                         "#[nails] must have an argument list",
                     ));
                 }
-                Meta::List(list) => list,
+                Err(e) => return Err(e),
             };
             if_proc_macro_diagnostics! {
-                if list.nested.is_empty() {
-                    list.paren_token.span.unwrap().warning("#[nails()] is meaningless").emit();
+                if args.is_empty() {
+                    _paren_token.span.unwrap().warning("#[nails()] is meaningless").emit();
                 }
             }
-            for item in &list.nested {
-                match item {
-                    NestedMeta::Meta(meta) => {
+            for arg in &args {
+                match arg {
+                    StructArg::Meta(NestedMeta::Meta(meta)) => {
                         ret.parse_inner(meta)?;
                     }
-                    NestedMeta::Lit(lit) => {
+                    StructArg::Meta(NestedMeta::Lit(lit)) => {
                         return Err(syn::Error::new(lit.span(), "unexpected literal"));
                     }
+                    StructArg::List { path, elems, .. } => {
+                        if path.is_ident("method") {
+                            ret.parse_method_list(arg.span(), elems)?;
+                        } else if path.is_ident("path") || path.is_ident("host") {
+                            return Err(syn::Error::new(
+                                arg.span(),
+                                format_args!(
+                                    "string value expected in #[nails({})]",
+                                    path_to_string(path),
+                                ),
+                            ));
+                        } else {
+                            return Err(syn::Error::new(
+                                path.span(),
+                                format_args!("unknown option: `{}`", path_to_string(path)),
+                            ));
+                        }
+                    }
                 }
             }
         }
@@ -66,8 +93,12 @@ impl StructAttrs {
         let name = meta.path();
         if name.is_ident("path") {
             self.parse_path(meta)
+        } else if name.is_ident("host") {
+            self.parse_host(meta)
         } else if name.is_ident("method") {
             self.parse_method(meta)
+        } else if name.is_ident("to_uri") {
+            self.parse_to_uri(meta)
         } else {
             return Err(syn::Error::new(
                 meta.span(),
@@ -109,52 +140,181 @@ impl StructAttrs {
         }
     }
 
-    fn parse_method(&mut self, meta: &Meta) -> syn::Result<()> {
+    fn parse_host(&mut self, meta: &Meta) -> syn::Result<()> {
         let lit = match meta {
             Meta::Path(path) => {
                 return Err(syn::Error::new(
                     path.span(),
-                    "string value expected in #[nails(method)]",
+                    "string value expected in #[nails(host)]",
                 ));
             }
             Meta::List(list) => {
                 return Err(syn::Error::new(
                     list.paren_token.span,
-                    "extra parentheses in #[nails(method)]",
+                    "extra parentheses in #[nails(host)]",
                 ));
             }
             Meta::NameValue(nv) => &nv.lit,
         };
         if let Lit::Str(lit) = lit {
-            if self.method.is_some() {
+            if self.host.is_some() {
                 return Err(syn::Error::new(
                     lit.span(),
-                    "multiple #[nails(method)] definitions",
+                    "multiple #[nails(host)] definitions",
                 ));
             }
-            let lit_str = lit.value();
-            let kind = match lit_str.as_str() {
-                "GET" => MethodKind::Get,
-                "GET_ONLY" => MethodKind::GetOnly,
-                "HEAD" => MethodKind::Head,
-                "POST" => MethodKind::Post,
-                "PUT" => MethodKind::Put,
-                "DELETE" => MethodKind::Delete,
-                "OPTIONS" => MethodKind::Options,
-                "PATCH" => MethodKind::Patch,
-                _ => return Err(syn::Error::new(lit.span(), "Unknown method type")),
-            };
-            self.method = Some(MethodInfo {
-                lit: lit.clone(),
-                kind,
-            });
+            self.host = Some(HostInfo { host: lit.clone() });
             Ok(())
         } else {
-            return Err(syn::Error::new(
+            Err(syn::Error::new(
                 lit.span(),
-                "string value expected in #[nails(path)]",
+                "string value expected in #[nails(host)]",
+            ))
+        }
+    }
+
+    fn parse_method(&mut self, meta: &Meta) -> syn::Result<()> {
+        let lit = match meta {
+            Meta::Path(path) => {
+                return Err(syn::Error::new(
+                    path.span(),
+                    "string value expected in #[nails(method)]",
+                ));
+            }
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(method)]",
+                ));
+            }
+            Meta::NameValue(nv) => &nv.lit,
+        };
+        let span = lit.span();
+        self.parse_method_list(span, std::iter::once(lit))
+    }
+
+    fn parse_to_uri(&mut self, meta: &Meta) -> syn::Result<()> {
+        let span = match meta {
+            Meta::Path(path) => path.span(),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(to_uri)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                return Err(syn::Error::new(
+                    nv.lit.span(),
+                    "no value expected in #[nails(to_uri)]",
+                ));
+            }
+        };
+        if self.to_uri.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(to_uri)] definitions",
+            ));
+        }
+        self.to_uri = Some(ToUriInfo { span });
+        Ok(())
+    }
+
+    fn parse_method_list<'a>(
+        &mut self,
+        span: Span,
+        lits: impl IntoIterator<Item = &'a Lit>,
+    ) -> syn::Result<()> {
+        if self.method.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(method)] definitions",
             ));
         }
+        let mut kinds = Vec::new();
+        for lit in lits {
+            let lit = if let Lit::Str(lit) = lit {
+                lit
+            } else {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    "string value expected in #[nails(method)]",
+                ));
+            };
+            let kind = MethodKind::parse(lit)?;
+            if kinds.contains(&kind) {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format_args!("duplicate method `{}`", lit.value()),
+                ));
+            }
+            kinds.push(kind);
+        }
+        if kinds.is_empty() {
+            return Err(syn::Error::new(
+                span,
+                "at least one method is needed in #[nails(method)]",
+            ));
+        }
+        self.method = Some(MethodInfo { kinds });
+        Ok(())
+    }
+}
+
+/// An item in `#[nails(...)]` on a struct.
+enum StructArg {
+    Meta(NestedMeta),
+    /// `name = [lit, ...]`
+    List {
+        path: syn::Path,
+        eq_token: Token![=],
+        bracket_token: token::Bracket,
+        elems: Punctuated<Lit, Token![,]>,
+    },
+}
+
+impl Parse for StructArg {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        if input.peek(Ident::peek_any) && input.peek2(Token![=]) && input.peek3(token::Bracket) {
+            let path = syn::Path::from(input.call(Ident::parse_any)?);
+            let eq_token = input.parse()?;
+            let content;
+            let bracket_token = syn::bracketed!(content in input);
+            let elems = Punctuated::parse_terminated(&content)?;
+            Ok(StructArg::List {
+                path,
+                eq_token,
+                bracket_token,
+                elems,
+            })
+        } else {
+            input.parse().map(StructArg::Meta)
+        }
+    }
+}
+
+impl ToTokens for StructArg {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            StructArg::Meta(meta) => meta.to_tokens(tokens),
+            StructArg::List {
+                path,
+                eq_token,
+                bracket_token,
+                elems,
+            } => {
+                path.to_tokens(tokens);
+                eq_token.to_tokens(tokens);
+                bracket_token.surround(tokens, |tokens| elems.to_tokens(tokens));
+            }
+        }
+    }
+}
+
+fn is_parenthesized(tokens: &TokenStream) -> bool {
+    let mut iter = tokens.clone().into_iter();
+    match (iter.next(), iter.next()) {
+        (Some(TokenTree::Group(group)), None) => group.delimiter() == Delimiter::Parenthesis,
+        _ => false,
     }
 }
 
@@ -163,13 +323,29 @@ pub(crate) struct PathInfo {
     pub(crate) path: LitStr,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HostInfo {
+    pub(crate) host: LitStr,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ToUriInfo {
+    pub(crate) span: Span,
+}
+
+impl PartialEq for ToUriInfo {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl Eq for ToUriInfo {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct MethodInfo {
-    pub(crate) lit: LitStr,
-    pub(crate) kind: MethodKind,
+    pub(crate) kinds: Vec<MethodKind>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum MethodKind {
     Get,
     GetOnly,
@@ -179,21 +355,78 @@ pub(crate) enum MethodKind {
     Delete,
     Options,
     Patch,
+    /// Any other method, such as WebDAV's `PROPFIND`.
+    Extension(String),
+}
+
+impl MethodKind {
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        let name = lit.value();
+        let kind = match name.as_str() {
+            "GET" => MethodKind::Get,
+            "GET_ONLY" => MethodKind::GetOnly,
+            "HEAD" => MethodKind::Head,
+            "POST" => MethodKind::Post,
+            "PUT" => MethodKind::Put,
+            "DELETE" => MethodKind::Delete,
+            "OPTIONS" => MethodKind::Options,
+            "PATCH" => MethodKind::Patch,
+            _ => {
+                if name.is_empty() || !name.bytes().all(is_tchar) {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        format_args!("invalid method name `{}`", name),
+                    ));
+                }
+                let upper = name.to_ascii_uppercase();
+                if upper != name
+                    && ["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS", "PATCH"]
+                        .contains(&upper.as_str())
+                {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        format_args!("method names are case-sensitive; use `{}`", upper),
+                    ));
+                }
+                MethodKind::Extension(name)
+            }
+        };
+        Ok(kind)
+    }
+}
+
+// tchar in RFC 7230
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct FieldAttrs {
     pub(crate) query: Option<QueryFieldInfo>,
+    pub(crate) query_struct: Option<QueryStructFieldInfo>,
     pub(crate) path: Option<PathFieldInfo>,
+    pub(crate) host: Option<HostFieldInfo>,
+    pub(crate) header: Option<HeaderFieldInfo>,
+    pub(crate) cookie: Option<CookieFieldInfo>,
+    pub(crate) session: Option<SessionFieldInfo>,
     pub(crate) body: Option<BodyFieldInfo>,
+    pub(crate) allow_encoded_slash: Option<AllowEncodedSlashInfo>,
+    pub(crate) default: Option<DefaultInfo>,
 }
 
 impl FieldAttrs {
     pub(crate) fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut ret = Self {
             query: None,
+            query_struct: None,
             path: None,
+            host: None,
+            header: None,
+            cookie: None,
+            session: None,
             body: None,
+            allow_encoded_slash: None,
+            default: None,
         };
         for attr in attrs {
             if !attr.path.is_ident("nails") {
@@ -238,10 +471,24 @@ impl FieldAttrs {
         let name = meta.path();
         if name.is_ident("query") {
             self.parse_query(meta)
+        } else if name.is_ident("query_struct") {
+            self.parse_query_struct(meta)
         } else if name.is_ident("path") {
             self.parse_path(meta)
+        } else if name.is_ident("host") {
+            self.parse_host(meta)
+        } else if name.is_ident("header") {
+            self.parse_header(meta)
+        } else if name.is_ident("cookie") {
+            self.parse_cookie(meta)
+        } else if name.is_ident("session") {
+            self.parse_session(meta)
         } else if name.is_ident("body") {
             self.parse_body(meta)
+        } else if name.is_ident("allow_encoded_slash") {
+            self.parse_allow_encoded_slash(meta)
+        } else if name.is_ident("default") {
+            self.parse_default(meta)
         } else {
             return Err(syn::Error::new(
                 meta.span(),
@@ -280,6 +527,36 @@ impl FieldAttrs {
         Ok(())
     }
 
+    fn parse_query_struct(&mut self, meta: &Meta) -> syn::Result<()> {
+        let (lit, span) = match meta {
+            Meta::Path(path) => (None, path.span()),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(query_struct)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                if let Lit::Str(lit) = &nv.lit {
+                    (Some(lit.clone()), nv.span())
+                } else {
+                    return Err(syn::Error::new(
+                        nv.lit.span(),
+                        "string value or no value expected in #[nails(query_struct)]",
+                    ));
+                }
+            }
+        };
+        if self.query_struct.is_some() {
+            return Err(syn::Error::new(
+                lit.span(),
+                "multiple #[nails(query_struct)] definitions",
+            ));
+        }
+        self.query_struct = Some(QueryStructFieldInfo { name: lit, span });
+        Ok(())
+    }
+
     fn parse_path(&mut self, meta: &Meta) -> syn::Result<()> {
         let (lit, span) = match meta {
             Meta::Path(path) => (None, path.span()),
@@ -310,6 +587,133 @@ impl FieldAttrs {
         Ok(())
     }
 
+    fn parse_host(&mut self, meta: &Meta) -> syn::Result<()> {
+        let (lit, span) = match meta {
+            Meta::Path(path) => (None, path.span()),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(host)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                if let Lit::Str(lit) = &nv.lit {
+                    (Some(lit.clone()), nv.span())
+                } else {
+                    return Err(syn::Error::new(
+                        nv.lit.span(),
+                        "string value or no value expected in #[nails(host)]",
+                    ));
+                }
+            }
+        };
+        if self.host.is_some() {
+            return Err(syn::Error::new(span, "multiple #[nails(host)] definitions"));
+        }
+        self.host = Some(HostFieldInfo { name: lit, span });
+        Ok(())
+    }
+
+    fn parse_header(&mut self, meta: &Meta) -> syn::Result<()> {
+        let (lit, span) = match meta {
+            Meta::Path(path) => (None, path.span()),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(header)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                if let Lit::Str(lit) = &nv.lit {
+                    let name = lit.value();
+                    if name.is_empty() || !name.bytes().all(is_tchar) {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            format_args!("invalid header name `{}`", name),
+                        ));
+                    }
+                    (Some(lit.clone()), nv.span())
+                } else {
+                    return Err(syn::Error::new(
+                        nv.lit.span(),
+                        "string value or no value expected in #[nails(header)]",
+                    ));
+                }
+            }
+        };
+        if self.header.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(header)] definitions",
+            ));
+        }
+        self.header = Some(HeaderFieldInfo { name: lit, span });
+        Ok(())
+    }
+
+    fn parse_cookie(&mut self, meta: &Meta) -> syn::Result<()> {
+        let (lit, span) = match meta {
+            Meta::Path(path) => (None, path.span()),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(cookie)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                if let Lit::Str(lit) = &nv.lit {
+                    let name = lit.value();
+                    if name.is_empty() || !name.bytes().all(is_tchar) {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            format_args!("invalid cookie name `{}`", name),
+                        ));
+                    }
+                    (Some(lit.clone()), nv.span())
+                } else {
+                    return Err(syn::Error::new(
+                        nv.lit.span(),
+                        "string value or no value expected in #[nails(cookie)]",
+                    ));
+                }
+            }
+        };
+        if self.cookie.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(cookie)] definitions",
+            ));
+        }
+        self.cookie = Some(CookieFieldInfo { name: lit, span });
+        Ok(())
+    }
+
+    fn parse_session(&mut self, meta: &Meta) -> syn::Result<()> {
+        let span = match meta {
+            Meta::Path(path) => path.span(),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(session)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                return Err(syn::Error::new(
+                    nv.lit.span(),
+                    "no value expected in #[nails(session)]",
+                ));
+            }
+        };
+        if self.session.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(session)] definitions",
+            ));
+        }
+        self.session = Some(SessionFieldInfo { span });
+        Ok(())
+    }
+
     fn parse_body(&mut self, meta: &Meta) -> syn::Result<()> {
         let span = match meta {
             Meta::Path(path) => path.span(),
@@ -332,6 +736,64 @@ impl FieldAttrs {
         self.body = Some(BodyFieldInfo { span });
         Ok(())
     }
+
+    fn parse_allow_encoded_slash(&mut self, meta: &Meta) -> syn::Result<()> {
+        let span = match meta {
+            Meta::Path(path) => path.span(),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(allow_encoded_slash)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                return Err(syn::Error::new(
+                    nv.lit.span(),
+                    "no value expected in #[nails(allow_encoded_slash)]",
+                ));
+            }
+        };
+        if self.allow_encoded_slash.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(allow_encoded_slash)] definitions",
+            ));
+        }
+        self.allow_encoded_slash = Some(AllowEncodedSlashInfo { span });
+        Ok(())
+    }
+
+    fn parse_default(&mut self, meta: &Meta) -> syn::Result<()> {
+        let (expr, span) = match meta {
+            Meta::Path(path) => (None, path.span()),
+            Meta::List(list) => {
+                return Err(syn::Error::new(
+                    list.paren_token.span,
+                    "extra parentheses in #[nails(default)]",
+                ));
+            }
+            Meta::NameValue(nv) => {
+                // `default = "expr"`, or a literal to be used as is.
+                let expr = if let Lit::Str(lit) = &nv.lit {
+                    lit.parse::<Expr>()?
+                } else {
+                    Expr::Lit(ExprLit {
+                        attrs: Vec::new(),
+                        lit: nv.lit.clone(),
+                    })
+                };
+                (Some(expr), nv.span())
+            }
+        };
+        if self.default.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "multiple #[nails(default)] definitions",
+            ));
+        }
+        self.default = Some(DefaultInfo { expr, span });
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -347,6 +809,19 @@ impl PartialEq for QueryFieldInfo {
 }
 impl Eq for QueryFieldInfo {}
 
+#[derive(Debug, Clone)]
+pub(crate) struct QueryStructFieldInfo {
+    pub(crate) name: Option<LitStr>,
+    pub(crate) span: Span,
+}
+
+impl PartialEq for QueryStructFieldInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+impl Eq for QueryStructFieldInfo {}
+
 #[derive(Debug, Clone)]
 pub(crate) struct PathFieldInfo {
     pub(crate) name: Option<LitStr>,
@@ -360,6 +835,57 @@ impl PartialEq for PathFieldInfo {
 }
 impl Eq for PathFieldInfo {}
 
+#[derive(Debug, Clone)]
+pub(crate) struct HostFieldInfo {
+    pub(crate) name: Option<LitStr>,
+    pub(crate) span: Span,
+}
+
+impl PartialEq for HostFieldInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+impl Eq for HostFieldInfo {}
+
+#[derive(Debug, Clone)]
+pub(crate) struct HeaderFieldInfo {
+    pub(crate) name: Option<LitStr>,
+    pub(crate) span: Span,
+}
+
+impl PartialEq for HeaderFieldInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+impl Eq for HeaderFieldInfo {}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CookieFieldInfo {
+    pub(crate) name: Option<LitStr>,
+    pub(crate) span: Span,
+}
+
+impl PartialEq for CookieFieldInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+impl Eq for CookieFieldInfo {}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SessionFieldInfo {
+    pub(crate) span: Span,
+}
+
+impl PartialEq for SessionFieldInfo {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl Eq for SessionFieldInfo {}
+
 #[derive(Debug, Clone)]
 pub(crate) struct BodyFieldInfo {
     pub(crate) span: Span,
@@ -372,6 +898,31 @@ impl PartialEq for BodyFieldInfo {
 }
 impl Eq for BodyFieldInfo {}
 
+#[derive(Debug, Clone)]
+pub(crate) struct AllowEncodedSlashInfo {
+    pub(crate) span: Span,
+}
+
+impl PartialEq for AllowEncodedSlashInfo {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl Eq for AllowEncodedSlashInfo {}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DefaultInfo {
+    pub(crate) expr: Option<Expr>,
+    pub(crate) span: Span,
+}
+
+impl PartialEq for DefaultInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+    }
+}
+impl Eq for DefaultInfo {}
+
 fn path_to_string(path: &syn::Path) -> String {
     use std::fmt::Write;
 
@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use nails_pattern::is_ident;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+/// A host pattern like `{tenant}.example.com`. Variables span whole labels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HostPattern {
+    labels: Vec<HostLabel>,
+    bindings: HashSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostLabel {
+    Literal(String),
+    Var(String),
+}
+
+impl HostPattern {
+    pub(crate) fn bindings(&self) -> &HashSet<String> {
+        &self.bindings
+    }
+
+    fn gen_labels(&self) -> TokenStream {
+        let labels = self.labels.iter().map(|label| match label {
+            HostLabel::Literal(s) => quote! { Some(#s) },
+            HostLabel::Var(_) => quote! { None },
+        });
+        quote! { &[#(#labels),*] }
+    }
+
+    /// Generates a condition on `host`, an `Option<&str>`.
+    pub(crate) fn gen_host_condition(
+        &self,
+        host: TokenStream,
+        fields: &HashMap<String, &syn::Field>,
+    ) -> TokenStream {
+        let labels = self.gen_labels();
+        let conditions = self
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, label)| match label {
+                HostLabel::Literal(_) => None,
+                HostLabel::Var(var) => {
+                    let field_ty = &fields[var].ty;
+                    Some(quote! {
+                        <#field_ty as nails::__rt::FromPath>::matches(labels[#i])
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+        if conditions.is_empty() {
+            quote! {
+                #host.and_then(|host| nails::__rt::match_host(host, #labels)).is_some()
+            }
+        } else {
+            quote! {
+                #host.and_then(|host| nails::__rt::match_host(host, #labels)).map(|labels| {
+                    #(#conditions)&&*
+                }).unwrap_or(false)
+            }
+        }
+    }
+
+    pub(crate) fn gen_host_extractor(
+        &self,
+        host: TokenStream,
+        fields: &HashMap<String, &syn::Field>,
+    ) -> (TokenStream, HashMap<String, syn::Ident>) {
+        let mut vars = HashMap::new();
+        let extractors = self
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, label)| match label {
+                HostLabel::Literal(_) => None,
+                HostLabel::Var(var) => {
+                    let var_ident = format!("hostcomp_{}", var);
+                    let var_ident = syn::Ident::new(&var_ident, Span::call_site());
+                    vars.insert(var.clone(), var_ident.clone());
+                    let field_ty = &fields[var].ty;
+                    Some(quote! {
+                        let #var_ident = <#field_ty as nails::__rt::FromPath>::from_path(
+                            host_labels[#i]
+                        ).expect("internal error: invalid host given");
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+        if extractors.is_empty() {
+            return (TokenStream::new(), vars);
+        }
+        let labels = self.gen_labels();
+        let extractor = quote! {
+            let host_labels = nails::__rt::match_host(
+                #host.expect("internal error: invalid host given"),
+                #labels,
+            ).expect("internal error: invalid host given");
+            #(#extractors)*
+        };
+        (extractor, vars)
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = ParseError;
+
+    fn from_str(host: &str) -> Result<Self, Self::Err> {
+        if host.is_empty() {
+            return Err(ParseError::new(host, "must not be empty"));
+        }
+        if host.contains([':', '/']) {
+            return Err(ParseError::new(host, "must not contain a port or a path"));
+        }
+        let mut bindings = HashSet::new();
+        let labels = host
+            .split('.')
+            .map(|label| -> Result<_, Self::Err> {
+                if label.is_empty() {
+                    return Err(ParseError::new(host, "empty host label"));
+                }
+                let inner = label
+                    .strip_prefix('{')
+                    .and_then(|label| label.strip_suffix('}'));
+                let name = if let Some(name) = inner {
+                    name
+                } else if label.contains(['{', '}']) {
+                    return Err(ParseError::new(
+                        host,
+                        "variable must span the whole host label",
+                    ));
+                } else {
+                    return Ok(HostLabel::Literal(label.to_ascii_lowercase()));
+                };
+                if name.is_empty() {
+                    return Err(ParseError::new(host, "variable must contain variable name"));
+                }
+                if !is_ident(name) {
+                    return Err(ParseError::new(
+                        host,
+                        "variable must be /[a-zA-Z_][a-zA-Z0-9_]*/",
+                    ));
+                }
+                if !bindings.insert(name.to_owned()) {
+                    return Err(ParseError::new(
+                        host,
+                        &format!("duplicate name: `{}`", name),
+                    ));
+                }
+                Ok(HostLabel::Var(name.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { labels, bindings })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    host: String,
+    message: String,
+}
+
+impl ParseError {
+    fn new(host: &str, message: &str) -> Self {
+        Self {
+            host: host.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "error while parsing host matcher `{}`: {}",
+            self.host, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+    use crate::assert_ts_eq;
+    use syn::parse::Parser;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "{tenant}.Example.com".parse::<HostPattern>().unwrap(),
+            HostPattern {
+                labels: vec![
+                    HostLabel::Var("tenant".to_owned()),
+                    HostLabel::Literal("example".to_owned()),
+                    HostLabel::Literal("com".to_owned()),
+                ],
+                bindings: vec!["tenant".to_owned()].into_iter().collect(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let parse_err = |host: &str| host.parse::<HostPattern>().unwrap_err().message;
+        assert_eq!(parse_err(""), "must not be empty");
+        assert_eq!(
+            parse_err("example.com:8080"),
+            "must not contain a port or a path"
+        );
+        assert_eq!(parse_err("example..com"), "empty host label");
+        assert_eq!(
+            parse_err("{tenant}-api.example.com"),
+            "variable must span the whole host label"
+        );
+        assert_eq!(
+            parse_err("{}.example.com"),
+            "variable must contain variable name"
+        );
+        assert_eq!(
+            parse_err("{1}.example.com"),
+            "variable must be /[a-zA-Z_][a-zA-Z0-9_]*/"
+        );
+        assert_eq!(parse_err("{a}.{a}.com"), "duplicate name: `a`");
+    }
+
+    #[test]
+    fn test_gen_host_condition() {
+        let field = syn::Field::parse_named
+            .parse2(quote! { tenant: String })
+            .unwrap();
+        let fields = vec![("tenant".to_owned(), &field)].into_iter().collect();
+        assert_ts_eq!(
+            "{tenant}.example.com"
+                .parse::<HostPattern>()
+                .unwrap()
+                .gen_host_condition(quote! { head.host() }, &fields),
+            quote! {
+                head.host().and_then(|host| nails::__rt::match_host(
+                    host,
+                    &[None, Some("example"), Some("com")]
+                )).map(|labels| {
+                    <String as nails::__rt::FromPath>::matches(labels[0usize])
+                }).unwrap_or(false)
+            },
+        );
+        assert_ts_eq!(
+            "api.example.com"
+                .parse::<HostPattern>()
+                .unwrap()
+                .gen_host_condition(quote! { head.host() }, &fields),
+            quote! {
+                head.host().and_then(|host| nails::__rt::match_host(
+                    host,
+                    &[Some("api"), Some("example"), Some("com")]
+                )).is_some()
+            },
+        );
+    }
+}
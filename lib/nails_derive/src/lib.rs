@@ -10,10 +10,12 @@ use syn::spanned::Spanned;
 use syn::DeriveInput;
 
 use crate::attrs::{FieldAttrs, StructAttrs};
+use crate::host::HostPattern;
 use crate::path::PathPattern;
-use crate::utils::FieldsExt;
+use crate::utils::{field_member, FieldsExt};
 
 mod attrs;
+mod host;
 mod path;
 mod utils;
 
@@ -31,93 +33,301 @@ pub fn derive_preroute(input: proc_macro::TokenStream) -> proc_macro::TokenStrea
 fn derive_preroute2(input: TokenStream) -> syn::Result<TokenStream> {
     let input = syn::parse2::<DeriveInput>(input)?;
 
-    let data = if let syn::Data::Struct(data) = &input.data {
-        data
+    match &input.data {
+        syn::Data::Struct(data) => derive_struct(&input, data),
+        syn::Data::Enum(data) => derive_enum(&input, data),
+        syn::Data::Union(_) => Err(syn::Error::new(
+            input.span(),
+            "Preroute cannot be derived for unions",
+        )),
+    }
+}
+
+fn derive_struct(input: &DeriveInput, data: &syn::DataStruct) -> syn::Result<TokenStream> {
+    let shape = Shape::parse(
+        &input.attrs,
+        &input.ident,
+        quote! {},
+        &data.fields,
+        input.span(),
+        &|member| quote! { &self.#member },
+    )?;
+    let Shape {
+        path_prefix,
+        route_pattern,
+        methods,
+        method_cond,
+        path_cond,
+        host_cond,
+        extractor,
+        construct,
+        uri_builder,
+        to_uri,
+        explicit_head,
+        ..
+    } = shape;
+    let match_head = if let Some(host_cond) = host_cond {
+        quote! {
+            fn match_head(head: &nails::__rt::RequestHead<'_>) -> bool {
+                #host_cond
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let explicit_head = if explicit_head {
+        quote! {
+            fn explicit_head(_head: &nails::__rt::RequestHead<'_>) -> bool {
+                true
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = &input.generics.split_for_impl();
+    let to_uri_impl = if to_uri.is_some() {
+        quote! {
+            impl #impl_generics nails::__rt::ToUri for #name #ty_generics #where_clause {
+                fn to_uri(&self) -> nails::__rt::Uri {
+                    #uri_builder
+                }
+            }
+        }
     } else {
+        quote! {}
+    };
+    Ok(quote! {
+        impl #impl_generics nails::__rt::Preroute for #name #ty_generics #where_clause {
+            fn path_prefix_hint() -> &'static str {
+                #path_prefix
+            }
+            fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                vec![#route_pattern]
+            }
+            fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                #method_cond && #path_cond
+            }
+            #match_head
+            #explicit_head
+            fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                if #path_cond {
+                    #methods
+                } else {
+                    Vec::new()
+                }
+            }
+
+            fn from_request<'a>(
+                req: nails::__rt::Request<nails::__rt::Body>
+            ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                nails::__rt::box_future(async move {
+                let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                let path = req.uri().path();
+                #extractor
+                Ok(#construct)
+                })
+            }
+        }
+
+        #to_uri_impl
+    })
+}
+
+fn derive_enum(input: &DeriveInput, data: &syn::DataEnum) -> syn::Result<TokenStream> {
+    // Only `to_uri` applies to the enum as a whole.
+    let enum_attrs = StructAttrs::parse(&input.attrs)?;
+    if enum_attrs.path.is_some() || enum_attrs.host.is_some() || enum_attrs.method.is_some() {
+        let attr = input
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("nails"))
+            .unwrap();
+        return Err(syn::Error::new(
+            attr.span(),
+            "#[nails] must be given on each variant of an enum",
+        ));
+    }
+    if data.variants.is_empty() {
         return Err(syn::Error::new(
             input.span(),
-            "Preroute cannot be derived for enums or unions",
+            "Preroute cannot be derived for enums without variants",
         ));
-    };
-    let attrs = StructAttrs::parse(&input.attrs)?;
-    let field_attrs = data
-        .fields
+    }
+    let name = &input.ident;
+    let shapes = data
+        .variants
         .iter()
-        .map(|field| FieldAttrs::parse(&field.attrs))
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let path = attrs
-        .path
-        .clone()
-        .ok_or_else(|| syn::Error::new(input.span(), "#[nails(path)] is needed"))?;
-    let path_span = path.path.span();
-    let path = path
-        .path
-        .value()
-        .parse::<PathPattern>()
-        .map_err(|e| syn::Error::new(path_span, e))?;
-
-    let field_len = data.fields.iter().len();
-    let field_kinds = data
-        .fields
+        .map(|variant| {
+            Shape::parse(
+                &variant.attrs,
+                &variant.ident,
+                quote! { #name:: },
+                &variant.fields,
+                variant.span(),
+                &|member| {
+                    let binding = member_binding(member);
+                    quote! { #binding }
+                },
+            )
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    if let Some(span) = shapes.iter().find_map(|shape| shape.to_uri) {
+        return Err(syn::Error::new(
+            span,
+            "#[nails(to_uri)] must be given on the enum",
+        ));
+    }
+
+    let path_prefix = shapes
         .iter()
-        .zip(&field_attrs)
-        .enumerate()
-        .map(|(i, (field, attrs))| {
-            FieldKind::parse_from(field, i + 1 == field_len, attrs, path.bindings())
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let path_fields = {
-        let mut path_fields = HashMap::new();
-        for (idx, field) in data.fields.iter().enumerate() {
-            if let FieldKind::Path { var } = &field_kinds[idx] {
-                if let Some(_dup_field) = path_fields.get(var) {
-                    let span = if let Some(path) = &field_attrs[idx].path {
-                        path.span
-                    } else {
-                        field.span()
-                    };
-                    return Err(syn::Error::new(span, "Duplicate path names"));
+        .map(|shape| &shape.path_prefix[..])
+        .fold(None, |prefix: Option<&str>, shape_prefix| {
+            Some(prefix.map_or(shape_prefix, |prefix| common_prefix(prefix, shape_prefix)))
+        })
+        .unwrap();
+    let route_patterns = shapes.iter().map(|shape| &shape.route_pattern);
+    let path_matchers = shapes.iter().map(|shape| {
+        let Shape {
+            method_cond,
+            path_cond,
+            ..
+        } = shape;
+        quote! { (#method_cond && #path_cond) }
+    });
+    let has_host = shapes.iter().any(|shape| shape.host_cond.is_some());
+    let match_head = if has_host {
+        // The host condition only holds together with the variant's method and path.
+        let head_matchers = shapes.iter().map(|shape| {
+            let Shape {
+                method_cond,
+                path_cond,
+                host_cond,
+                ..
+            } = shape;
+            let host_cond = host_cond.clone().unwrap_or_else(|| quote! { true });
+            quote! { (#method_cond && #path_cond && #host_cond) }
+        });
+        quote! {
+            fn match_head(head: &nails::__rt::RequestHead<'_>) -> bool {
+                let method = head.method();
+                let path = head.path();
+                #(#head_matchers)||*
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let explicit_head = if shapes.iter().any(|shape| shape.explicit_head) {
+        // Decided by the variant `from_request` would pick.
+        let head_checks = shapes.iter().map(|shape| {
+            let Shape {
+                method_cond,
+                path_cond,
+                host_cond,
+                explicit_head,
+                ..
+            } = shape;
+            let host_cond = host_cond.as_ref().map(|host_cond| quote! { && #host_cond });
+            quote! {
+                if #method_cond && #path_cond #host_cond {
+                    return #explicit_head;
                 }
-                path_fields.insert(var.clone(), field);
+            }
+        });
+        quote! {
+            fn explicit_head(head: &nails::__rt::RequestHead<'_>) -> bool {
+                let method = head.method();
+                let path = head.path();
+                #(#head_checks)*
+                false
             }
         }
-        path_fields
+    } else {
+        quote! {}
     };
-    for binding in path.bindings() {
-        if !path_fields.contains_key(binding) {
-            return Err(syn::Error::new(
-                path_span,
-                format_args!("Missing field for binding name from {{{}}}", binding),
-            ));
+    let method_collectors = shapes.iter().map(|shape| {
+        let Shape {
+            path_cond, methods, ..
+        } = shape;
+        quote! {
+            if #path_cond {
+                for method in #methods {
+                    if !methods.contains(&method) {
+                        methods.push(method);
+                    }
+                }
+            }
+        }
+    });
+    let head_decl = if has_host {
+        quote! {
+            let head = nails::__rt::RequestHead::from_request(&req);
         }
-    }
-
-    let path_prefix = path.path_prefix();
-    let path_condition = path.gen_path_condition(quote! { path }, &path_fields);
-    let (path_extractor, path_vars) = path.gen_path_extractor(quote! { path }, &path_fields);
-
-    let construct = data.fields.try_construct(&input.ident, |field, idx| {
-        field_kinds[idx].gen_parser(field, &path_vars)
-    })?;
-
-    let method_cond = if let Some(method) = attrs.method {
-        method.kind
     } else {
-        attrs::MethodKind::Get
-    }
-    .gen_condition(quote! { method });
-
-    let name = &input.ident;
+        quote! {}
+    };
+    let variant_builders = shapes.iter().map(|shape| {
+        let Shape {
+            method_cond,
+            path_cond,
+            host_cond,
+            extractor,
+            construct,
+            ..
+        } = shape;
+        let host_cond = host_cond.as_ref().map(|host_cond| quote! { && #host_cond });
+        quote! {
+            if #method_cond && #path_cond #host_cond {
+                #extractor
+                return Ok(#construct);
+            }
+        }
+    });
     let (impl_generics, ty_generics, where_clause) = &input.generics.split_for_impl();
+    let to_uri_impl = if enum_attrs.to_uri.is_some() {
+        let uri_arms = data.variants.iter().zip(&shapes).map(|(variant, shape)| {
+            let variant_name = &variant.ident;
+            let members = shape.uri_members.iter();
+            let bindings = shape.uri_members.iter().map(member_binding);
+            let uri_builder = &shape.uri_builder;
+            quote! {
+                #name::#variant_name { #(#members: #bindings,)* .. } => {
+                    #uri_builder
+                }
+            }
+        });
+        quote! {
+            impl #impl_generics nails::__rt::ToUri for #name #ty_generics #where_clause {
+                fn to_uri(&self) -> nails::__rt::Uri {
+                    match self {
+                        #(#uri_arms)*
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     Ok(quote! {
         impl #impl_generics nails::__rt::Preroute for #name #ty_generics #where_clause {
             fn path_prefix_hint() -> &'static str {
                 #path_prefix
             }
+            fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                vec![#(#route_patterns),*]
+            }
             fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
-                #method_cond && #path_condition
+                #(#path_matchers)||*
+            }
+            #match_head
+            #explicit_head
+            fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                let mut methods = Vec::new();
+                #(#method_collectors)*
+                methods
             }
 
             fn from_request<'a>(
@@ -126,43 +336,397 @@ fn derive_preroute2(input: TokenStream) -> syn::Result<TokenStream> {
                 nails::__rt::box_future(async move {
                 let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
                 let path = req.uri().path();
-                #path_extractor
-                Ok(#construct)
+                let method = req.method();
+                #head_decl
+                #(#variant_builders)*
+                panic!("internal error: no variant matches the request")
                 })
             }
         }
+
+        #to_uri_impl
     })
 }
 
-impl attrs::MethodKind {
-    fn gen_condition(&self, method_var: TokenStream) -> TokenStream {
-        use attrs::MethodKind::*;
+fn member_binding(member: &syn::Member) -> syn::Ident {
+    let name = match member {
+        syn::Member::Named(ident) => format!("__field_{}", ident),
+        syn::Member::Unnamed(index) => format!("__field_{}", index.index),
+    };
+    syn::Ident::new(&name, Span::call_site())
+}
+
+/// The longest common prefix, not cutting a character in half.
+fn common_prefix<'a>(lhs: &'a str, rhs: &str) -> &'a str {
+    let len = lhs
+        .char_indices()
+        .zip(rhs.chars())
+        .find(|&((_, l), r)| l != r)
+        .map_or(lhs.len().min(rhs.len()), |((i, _), _)| i);
+    &lhs[..len]
+}
 
-        let method_const = match *self {
-            Get => {
-                return quote! {
-                    (*#method_var == nails::__rt::Method::GET || *#method_var == nails::__rt::Method::HEAD)
+/// The code generated for a struct or an enum variant.
+struct Shape {
+    path_prefix: String,
+    route_pattern: TokenStream,
+    methods: TokenStream,
+    /// A condition on `method: &Method`.
+    method_cond: TokenStream,
+    /// A condition on `path: &str`.
+    path_cond: TokenStream,
+    /// A condition on `head: &RequestHead`, if the host is restricted.
+    host_cond: Option<TokenStream>,
+    /// Statements extracting the fields from `req`, `path` and `query_hash`.
+    extractor: TokenStream,
+    construct: TokenStream,
+    /// Fields read by `uri_builder`.
+    uri_members: Vec<syn::Member>,
+    uri_builder: TokenStream,
+    /// Where `#[nails(to_uri)]` is given, if any.
+    to_uri: Option<Span>,
+    /// Whether HEAD is listed in `method`, rather than implied by GET.
+    explicit_head: bool,
+}
+
+impl Shape {
+    fn parse(
+        attrs: &[syn::Attribute],
+        ident: &syn::Ident,
+        ctor_prefix: TokenStream,
+        fields: &syn::Fields,
+        span: Span,
+        field_value: &dyn Fn(&syn::Member) -> TokenStream,
+    ) -> syn::Result<Self> {
+        let attrs = StructAttrs::parse(attrs)?;
+        let field_attrs = fields
+            .iter()
+            .map(|field| FieldAttrs::parse(&field.attrs))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let path = attrs
+            .path
+            .clone()
+            .ok_or_else(|| syn::Error::new(span, "#[nails(path)] is needed"))?;
+        let path_span = path.path.span();
+        let path_str = path.path.value();
+        let mut path = path_str
+            .parse::<PathPattern>()
+            .map_err(|e| syn::Error::new(path_span, e))?;
+
+        let host = if let Some(host) = &attrs.host {
+            let host_span = host.host.span();
+            let host = host
+                .host
+                .value()
+                .parse::<HostPattern>()
+                .map_err(|e| syn::Error::new(host_span, e))?;
+            let mut shared = host
+                .bindings()
+                .intersection(path.bindings())
+                .collect::<Vec<_>>();
+            shared.sort();
+            if let Some(name) = shared.first() {
+                return Err(syn::Error::new(
+                    host_span,
+                    format_args!("`{}` is bound in both the host and the path", name),
+                ));
+            }
+            Some((host, host_span))
+        } else {
+            None
+        };
+        let no_bindings = HashSet::new();
+        let host_bindings = host
+            .as_ref()
+            .map_or(&no_bindings, |(host, _)| host.bindings());
+
+        let field_len = fields.iter().len();
+        let field_kinds = fields
+            .iter()
+            .zip(&field_attrs)
+            .enumerate()
+            .map(|(i, (field, attrs))| {
+                FieldKind::parse_from(
+                    field,
+                    i + 1 == field_len,
+                    attrs,
+                    path.bindings(),
+                    host_bindings,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let path_fields = {
+            let mut path_fields = HashMap::new();
+            for (idx, field) in fields.iter().enumerate() {
+                if let FieldKind::Path { var } = &field_kinds[idx] {
+                    if let Some(_dup_field) = path_fields.get(var) {
+                        let span = if let Some(path) = &field_attrs[idx].path {
+                            path.span
+                        } else {
+                            field.span()
+                        };
+                        return Err(syn::Error::new(span, "Duplicate path names"));
+                    }
+                    path_fields.insert(var.clone(), field);
                 }
             }
-            GetOnly => "GET",
-            Head => "HEAD",
-            Post => "POST",
-            Put => "PUT",
-            Delete => "DELETE",
-            Options => "OPTIONS",
-            Patch => "PATCH",
+            path_fields
         };
-        let method_const = syn::Ident::new(method_const, Span::call_site());
-        quote! {
-            (*#method_var == nails::__rt::Method::#method_const)
+        let host_fields = {
+            let mut host_fields = HashMap::new();
+            for (idx, field) in fields.iter().enumerate() {
+                if let FieldKind::Host { var } = &field_kinds[idx] {
+                    if host_fields.contains_key(var) {
+                        let span = if let Some(host) = &field_attrs[idx].host {
+                            host.span
+                        } else {
+                            field.span()
+                        };
+                        return Err(syn::Error::new(span, "Duplicate host names"));
+                    }
+                    host_fields.insert(var.clone(), field);
+                }
+            }
+            host_fields
+        };
+        for (idx, attrs) in field_attrs.iter().enumerate() {
+            if let Some(info) = &attrs.allow_encoded_slash {
+                if let FieldKind::Path { var } = &field_kinds[idx] {
+                    path.allow_encoded_slash(var);
+                } else {
+                    return Err(syn::Error::new(
+                        info.span,
+                        "#[nails(allow_encoded_slash)] is only allowed for path fields",
+                    ));
+                }
+            }
+        }
+        for binding in path.bindings() {
+            if !path_fields.contains_key(binding) {
+                return Err(syn::Error::new(
+                    path_span,
+                    format_args!("Missing field for binding name from {{{}}}", binding),
+                ));
+            }
+        }
+
+        if let Some((host, host_span)) = &host {
+            let mut bindings = host.bindings().iter().collect::<Vec<_>>();
+            bindings.sort();
+            for binding in bindings {
+                if !host_fields.contains_key(binding) {
+                    return Err(syn::Error::new(
+                        *host_span,
+                        format_args!("Missing field for binding name from {{{}}}", binding),
+                    ));
+                }
+            }
+        }
+
+        let path_prefix = path.path_prefix();
+        let path_cond = path.gen_path_condition(quote! { path }, &path_fields);
+        let (path_extractor, path_vars) = path.gen_path_extractor(quote! { path }, &path_fields);
+        let (route_host, host_cond, host_extractor, host_vars) = if let Some((host, _)) = &host {
+            let host_str = attrs.host.as_ref().unwrap().host.value();
+            let host_cond = host.gen_host_condition(quote! { head.host() }, &host_fields);
+            let (host_extractor, host_vars) =
+                host.gen_host_extractor(quote! { nails::__rt::request_host(&req) }, &host_fields);
+            (
+                quote! { Some(#host_str) },
+                Some(host_cond),
+                host_extractor,
+                host_vars,
+            )
+        } else {
+            (quote! { None }, None, quote! {}, HashMap::new())
+        };
+
+        let mut path_values = HashMap::new();
+        let mut query_pushes = Vec::new();
+        let mut uri_members = Vec::new();
+        for (idx, field) in fields.iter().enumerate() {
+            let member = field_member(field, idx);
+            match &field_kinds[idx] {
+                FieldKind::Path { var } => {
+                    path_values.insert(var.clone(), field_value(&member));
+                    uri_members.push(member);
+                }
+                FieldKind::Query { name, .. } => {
+                    let value = field_value(&member);
+                    query_pushes.push(quote! {
+                        for value in nails::__rt::ToQuery::to_query(#value) {
+                            query.push((#name, value));
+                        }
+                    });
+                    uri_members.push(member);
+                }
+                FieldKind::QueryStruct { name } => {
+                    let value = field_value(&member);
+                    let pairs = syn::Ident::new(&format!("query_pairs_{}", idx), Span::call_site());
+                    query_pushes.push(quote! {
+                        let #pairs = nails::__rt::ToQueryStruct::to_query_struct(#value, #name);
+                        query.extend(#pairs.iter().map(|(key, value)| (key.as_str(), value.clone())));
+                    });
+                    uri_members.push(member);
+                }
+                FieldKind::Host { .. }
+                | FieldKind::Header { .. }
+                | FieldKind::Cookie { .. }
+                | FieldKind::Session
+                | FieldKind::Body => {}
+            }
+        }
+        let path_builder = path.gen_path_builder(quote! { path }, &path_values);
+        let uri_builder = if query_pushes.is_empty() {
+            quote! {
+                #path_builder
+                nails::__rt::build_uri(path, &[])
+            }
+        } else {
+            quote! {
+                #path_builder
+                let mut query = Vec::new();
+                #(#query_pushes)*
+                nails::__rt::build_uri(path, &query)
+            }
+        };
+
+        let cookie_parser = if field_kinds
+            .iter()
+            .any(|kind| matches!(kind, FieldKind::Cookie { .. }))
+        {
+            quote! {
+                let cookie_hash = nails::__rt::parse_cookies(req.headers());
+            }
+        } else {
+            quote! {}
+        };
+
+        let query_tree_parser = if field_kinds
+            .iter()
+            .any(|kind| matches!(kind, FieldKind::QueryStruct { .. }))
+        {
+            quote! {
+                let query_tree = nails::__rt::parse_nested_query(req.uri().query().unwrap_or(""));
+            }
+        } else {
+            quote! {}
+        };
+
+        let construct = fields.try_construct(ident, |field, idx| {
+            field_kinds[idx].gen_parser(field, &path_vars, &host_vars)
+        })?;
+
+        let method_kinds = if let Some(method) = attrs.method {
+            method.kinds
+        } else {
+            vec![attrs::MethodKind::Get]
+        };
+        let explicit_head = method_kinds.contains(&attrs::MethodKind::Head);
+        let method_exprs = gen_method_exprs(&method_kinds);
+        let method_cond = gen_method_condition(&method_exprs, quote! { method });
+        let methods = quote! {
+            vec![#(#method_exprs,)*]
+        };
+
+        Ok(Self {
+            path_prefix,
+            route_pattern: quote! {
+                nails::__rt::RoutePattern {
+                    methods: #methods,
+                    host: #route_host,
+                    path: #path_str,
+                }
+            },
+            methods,
+            method_cond,
+            path_cond,
+            host_cond,
+            extractor: quote! {
+                #path_extractor
+                #host_extractor
+                #query_tree_parser
+                #cookie_parser
+            },
+            construct: quote! { #ctor_prefix #construct },
+            uri_members,
+            uri_builder,
+            to_uri: attrs.to_uri.map(|to_uri| to_uri.span),
+            explicit_head,
+        })
+    }
+}
+
+impl attrs::MethodKind {
+    fn method_names(&self) -> Vec<&str> {
+        use attrs::MethodKind::*;
+
+        match self {
+            Get => vec!["GET", "HEAD"],
+            GetOnly => vec!["GET"],
+            Head => vec!["HEAD"],
+            Post => vec!["POST"],
+            Put => vec!["PUT"],
+            Delete => vec!["DELETE"],
+            Options => vec!["OPTIONS"],
+            Patch => vec!["PATCH"],
+            Extension(name) => vec![name],
+        }
+    }
+}
+
+fn gen_method_exprs(kinds: &[attrs::MethodKind]) -> Vec<TokenStream> {
+    let mut names = Vec::new();
+    for name in kinds.iter().flat_map(attrs::MethodKind::method_names) {
+        if !names.contains(&name) {
+            names.push(name);
         }
     }
+    names
+        .into_iter()
+        .map(|name| match name {
+            "GET" | "HEAD" | "POST" | "PUT" | "DELETE" | "OPTIONS" | "PATCH" => {
+                let method_const = syn::Ident::new(name, Span::call_site());
+                quote! { nails::__rt::Method::#method_const }
+            }
+            _ => {
+                let name = syn::LitByteStr::new(name.as_bytes(), Span::call_site());
+                quote! { nails::__rt::Method::from_bytes(#name).unwrap() }
+            }
+        })
+        .collect()
+}
+
+fn gen_method_condition(method_exprs: &[TokenStream], method_var: TokenStream) -> TokenStream {
+    quote! {
+        (#(*#method_var == #method_exprs)||*)
+    }
 }
 
 #[derive(Debug)]
 enum FieldKind {
-    Path { var: String },
-    Query { name: String },
+    Path {
+        var: String,
+    },
+    Host {
+        var: String,
+    },
+    Header {
+        name: String,
+    },
+    Cookie {
+        name: String,
+    },
+    Session,
+    Query {
+        name: String,
+        default: Option<TokenStream>,
+    },
+    QueryStruct {
+        name: String,
+    },
     Body,
 }
 
@@ -172,14 +736,61 @@ impl FieldKind {
         is_last: bool,
         attrs: &FieldAttrs,
         path_bindings: &HashSet<String>,
+        host_bindings: &HashSet<String>,
+    ) -> syn::Result<FieldKind> {
+        let kind = Self::parse_kind(field, is_last, attrs, path_bindings, host_bindings)?;
+        let default = if let Some(default) = &attrs.default {
+            default
+        } else {
+            return Ok(kind);
+        };
+        if let FieldKind::Query { name, .. } = kind {
+            let default = if let Some(expr) = &default.expr {
+                quote! { #expr }
+            } else {
+                quote! { Default::default() }
+            };
+            Ok(FieldKind::Query {
+                name,
+                default: Some(default),
+            })
+        } else {
+            Err(syn::Error::new(
+                default.span,
+                "#[nails(default)] is only allowed for query fields",
+            ))
+        }
+    }
+
+    fn parse_kind(
+        field: &syn::Field,
+        is_last: bool,
+        attrs: &FieldAttrs,
+        path_bindings: &HashSet<String>,
+        host_bindings: &HashSet<String>,
     ) -> syn::Result<FieldKind> {
         let mut specs = Vec::new();
         if let Some(query) = &attrs.query {
             specs.push(("query", query.span));
         }
+        if let Some(query_struct) = &attrs.query_struct {
+            specs.push(("query_struct", query_struct.span));
+        }
         if let Some(path) = &attrs.path {
             specs.push(("path", path.span));
         }
+        if let Some(host) = &attrs.host {
+            specs.push(("host", host.span));
+        }
+        if let Some(header) = &attrs.header {
+            specs.push(("header", header.span));
+        }
+        if let Some(cookie) = &attrs.cookie {
+            specs.push(("cookie", cookie.span));
+        }
+        if let Some(session) = &attrs.session {
+            specs.push(("session", session.span));
+        }
         if let Some(body) = &attrs.body {
             specs.push(("body", body.span));
         }
@@ -203,7 +814,24 @@ impl FieldKind {
                     "Specify name with #[nails(query = \"\")]",
                 ));
             };
-            return Ok(FieldKind::Query { name: query_name });
+            return Ok(FieldKind::Query {
+                name: query_name,
+                default: None,
+            });
+        }
+
+        if let Some(query_struct) = &attrs.query_struct {
+            let query_name = if let Some(query_name) = &query_struct.name {
+                query_name.value()
+            } else if let Some(ident) = &field.ident {
+                ident.to_string()
+            } else {
+                return Err(syn::Error::new(
+                    query_struct.span,
+                    "Specify name with #[nails(query_struct = \"\")]",
+                ));
+            };
+            return Ok(FieldKind::QueryStruct { name: query_name });
         }
 
         if let Some(path) = &attrs.path {
@@ -226,6 +854,61 @@ impl FieldKind {
             return Ok(FieldKind::Path { var: path_name });
         }
 
+        if let Some(host) = &attrs.host {
+            let host_name = if let Some(host_name) = &host.name {
+                host_name.value()
+            } else if let Some(ident) = &field.ident {
+                ident.to_string()
+            } else {
+                return Err(syn::Error::new(
+                    host.span,
+                    "Specify name with #[nails(host = \"\")]",
+                ));
+            };
+            if !host_bindings.contains(&host_name) {
+                return Err(syn::Error::new(
+                    host.span,
+                    "This name doesn't exist in the endpoint host",
+                ));
+            }
+            return Ok(FieldKind::Host { var: host_name });
+        }
+
+        if let Some(header) = &attrs.header {
+            let header_name = if let Some(header_name) = &header.name {
+                header_name.value()
+            } else if let Some(ident) = &field.ident {
+                // `if_none_match` -> `if-none-match`
+                ident.to_string().replace('_', "-")
+            } else {
+                return Err(syn::Error::new(
+                    header.span,
+                    "Specify name with #[nails(header = \"\")]",
+                ));
+            };
+            return Ok(FieldKind::Header {
+                name: header_name.to_ascii_lowercase(),
+            });
+        }
+
+        if let Some(cookie) = &attrs.cookie {
+            let cookie_name = if let Some(cookie_name) = &cookie.name {
+                cookie_name.value()
+            } else if let Some(ident) = &field.ident {
+                ident.to_string()
+            } else {
+                return Err(syn::Error::new(
+                    cookie.span,
+                    "Specify name with #[nails(cookie = \"\")]",
+                ));
+            };
+            return Ok(FieldKind::Cookie { name: cookie_name });
+        }
+
+        if attrs.session.is_some() {
+            return Ok(FieldKind::Session);
+        }
+
         if let Some(body) = &attrs.body {
             if !is_last {
                 return Err(syn::Error::new(
@@ -247,9 +930,15 @@ impl FieldKind {
         if path_bindings.contains(&ident_name) {
             // fallback to path
             Ok(FieldKind::Path { var: ident_name })
+        } else if host_bindings.contains(&ident_name) {
+            // fallback to host
+            Ok(FieldKind::Host { var: ident_name })
         } else {
             // fallback to query
-            Ok(FieldKind::Query { name: ident_name })
+            Ok(FieldKind::Query {
+                name: ident_name,
+                default: None,
+            })
         }
     }
 
@@ -257,13 +946,21 @@ impl FieldKind {
         &self,
         _field: &syn::Field,
         path_vars: &HashMap<String, syn::Ident>,
+        host_vars: &HashMap<String, syn::Ident>,
     ) -> syn::Result<TokenStream> {
         Ok(match self {
             FieldKind::Path { var } => {
                 let path_var = &path_vars[var];
                 quote! { #path_var }
             }
-            FieldKind::Query { name } => quote! {
+            FieldKind::Host { var } => {
+                let host_var = &host_vars[var];
+                quote! { #host_var }
+            }
+            FieldKind::Query {
+                name,
+                default: None,
+            } => quote! {
                 nails::__rt::FromQuery::from_query(
                     if let Some(values) = query_hash.get(#name) {
                         values.as_slice()
@@ -272,51 +969,834 @@ impl FieldKind {
                     }
                 )?
             },
-            FieldKind::Body => quote! {
-                nails::__rt::FromBody::from_body(
-                    req // TODO: abstract over ident name
+            FieldKind::Query {
+                name,
+                default: Some(default),
+            } => quote! {
+                if let Some(values) = query_hash.get(#name) {
+                    nails::__rt::FromQuery::from_query(values.as_slice())?
+                } else {
+                    #default
+                }
+            },
+            FieldKind::QueryStruct { name } => quote! {
+                nails::__rt::FromQueryStruct::from_query_struct(query_tree.get(#name))?
+            },
+            FieldKind::Header { name } => quote! {
+                nails::__rt::FromHeader::from_header(
+                    &req.headers().get_all(#name).iter().collect::<Vec<_>>()
+                )?
+            },
+            FieldKind::Cookie { name } => quote! {
+                nails::__rt::FromCookie::from_cookie(
+                    if let Some(values) = cookie_hash.get(#name) {
+                        values.as_slice()
+                    } else {
+                        &[]
+                    }
+                )?
+            },
+            FieldKind::Session => quote! {
+                nails::__rt::FromSession::from_session(
+                    req.extensions().get::<nails::__rt::SessionLoader>()
+                ).await?
+            },
+            FieldKind::Body => quote! {
+                nails::__rt::FromBody::from_body(
+                    req // TODO: abstract over ident name
                 ).await?
             },
         })
     }
-}
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive1() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(path = "/api/posts/{id}", to_uri)]
+                struct GetPostRequest {
+                    id: String,
+                    #[nails(query)]
+                    param1: String,
+                    #[nails(query = "param2rename")]
+                    param2: String,
+                    param3: String,
+                }
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for GetPostRequest {
+                    fn path_prefix_hint() -> &'static str { "/api/posts/" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/api/posts/{id}",
+                        }]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
+                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                    && path_iter.next().map(|comp| {
+                                        <String as nails::__rt::FromPath>::matches_raw(comp, false)
+                                    }).unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        )
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
+                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                    && path_iter.next().map(|comp| {
+                                        <String as nails::__rt::FromPath>::matches_raw(comp, false)
+                                    }).unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let mut path_iter = path[1..].split("/");
+                        path_iter.next();
+                        path_iter.next();
+                        let pathcomp_id = <String as nails::__rt::FromPath>::from_raw_path(
+                            path_iter.next().expect("internal error: invalid path given"),
+                            false,
+                        ).expect("internal error: invalid path given");
+                        Ok(GetPostRequest {
+                            id: pathcomp_id,
+                            param1: nails::__rt::FromQuery::from_query(
+                                if let Some(values) = query_hash.get("param1") {
+                                    values.as_slice()
+                                } else {
+                                    &[]
+                                }
+                            )?,
+                            param2: nails::__rt::FromQuery::from_query(
+                                if let Some(values) = query_hash.get("param2rename") {
+                                    values.as_slice()
+                                } else {
+                                    &[]
+                                }
+                            )?,
+                            param3: nails::__rt::FromQuery::from_query(
+                                if let Some(values) = query_hash.get("param3") {
+                                    values.as_slice()
+                                } else {
+                                    &[]
+                                }
+                            )?,
+                        })
+                        })
+                    }
+                }
+                impl nails::__rt::ToUri for GetPostRequest {
+                    fn to_uri(&self) -> nails::__rt::Uri {
+                        let mut path = String::new();
+                        path.push_str("/api");
+                        path.push_str("/posts");
+                        path.push_str("/");
+                        path.push_str(&nails::__rt::ToPath::to_raw_path(&self.id));
+                        let mut query = Vec::new();
+                        for value in nails::__rt::ToQuery::to_query(&self.param1) {
+                            query.push(("param1", value));
+                        }
+                        for value in nails::__rt::ToQuery::to_query(&self.param2) {
+                            query.push(("param2rename", value));
+                        }
+                        for value in nails::__rt::ToQuery::to_query(&self.param3) {
+                            query.push(("param3", value));
+                        }
+                        nails::__rt::build_uri(path, &query)
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_post() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(path = "/api/posts", method = "POST")]
+                struct CreatePostRequest;
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for CreatePostRequest {
+                    fn path_prefix_hint() -> &'static str { "/api/posts" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::POST,],
+                            host: None,
+                            path: "/api/posts",
+                        }]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (*method == nails::__rt::Method::POST) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
+                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        )
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
+                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::POST,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let mut path_iter = path[1..].split("/");
+                        path_iter.next();
+                        path_iter.next();
+                        Ok(CreatePostRequest)
+                        })
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_enum() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(to_uri)]
+                enum PingRequest {
+                    #[nails(path = "/ping")]
+                    Get,
+                    #[nails(path = "/ping/{id}", method = "DELETE")]
+                    Delete {
+                        id: u64,
+                        reason: Option<String>,
+                        #[nails(header)]
+                        x_request_id: Option<String>,
+                    },
+                }
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for PingRequest {
+                    fn path_prefix_hint() -> &'static str { "/ping" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![
+                            nails::__rt::RoutePattern {
+                                methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                                host: None,
+                                path: "/ping",
+                            },
+                            nails::__rt::RoutePattern {
+                                methods: vec![nails::__rt::Method::DELETE,],
+                                host: None,
+                                path: "/ping/{id}",
+                            }
+                        ]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (
+                            (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
+                                path.starts_with("/") && {
+                                    let mut path_iter = path[1..].split("/");
+                                    path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                        && path_iter.next().is_none()
+                                }
+                            )
+                        ) || (
+                            (*method == nails::__rt::Method::DELETE) && (
+                                path.starts_with("/") && {
+                                    let mut path_iter = path[1..].split("/");
+                                    path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                        && path_iter.next().map(|comp| {
+                                            <u64 as nails::__rt::FromPath>::matches_raw(comp, false)
+                                        }).unwrap_or(false)
+                                        && path_iter.next().is_none()
+                                }
+                            )
+                        )
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        let mut methods = Vec::new();
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            for method in vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,] {
+                                if !methods.contains(&method) {
+                                    methods.push(method);
+                                }
+                            }
+                        }
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().map(|comp| {
+                                        <u64 as nails::__rt::FromPath>::matches_raw(comp, false)
+                                    }).unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            for method in vec![nails::__rt::Method::DELETE,] {
+                                if !methods.contains(&method) {
+                                    methods.push(method);
+                                }
+                            }
+                        }
+                        methods
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let method = req.method();
+                        if (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            let mut path_iter = path[1..].split("/");
+                            path_iter.next();
+                            return Ok(PingRequest::Get);
+                        }
+                        if (*method == nails::__rt::Method::DELETE) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().map(|comp| {
+                                        <u64 as nails::__rt::FromPath>::matches_raw(comp, false)
+                                    }).unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            let mut path_iter = path[1..].split("/");
+                            path_iter.next();
+                            let pathcomp_id = <u64 as nails::__rt::FromPath>::from_raw_path(
+                                path_iter.next().expect("internal error: invalid path given"),
+                                false,
+                            ).expect("internal error: invalid path given");
+                            return Ok(PingRequest::Delete {
+                                id: pathcomp_id,
+                                reason: nails::__rt::FromQuery::from_query(
+                                    if let Some(values) = query_hash.get("reason") {
+                                        values.as_slice()
+                                    } else {
+                                        &[]
+                                    }
+                                )?,
+                                x_request_id: nails::__rt::FromHeader::from_header(
+                                    &req.headers().get_all("x-request-id").iter().collect::<Vec<_>>()
+                                )?,
+                            });
+                        }
+                        panic!("internal error: no variant matches the request")
+                        })
+                    }
+                }
+                impl nails::__rt::ToUri for PingRequest {
+                    fn to_uri(&self) -> nails::__rt::Uri {
+                        match self {
+                            PingRequest::Get { .. } => {
+                                let mut path = String::new();
+                                path.push_str("/ping");
+                                nails::__rt::build_uri(path, &[])
+                            }
+                            PingRequest::Delete { id: __field_id, reason: __field_reason, .. } => {
+                                let mut path = String::new();
+                                path.push_str("/ping");
+                                path.push_str("/");
+                                path.push_str(&nails::__rt::ToPath::to_raw_path(__field_id));
+                                let mut query = Vec::new();
+                                for value in nails::__rt::ToQuery::to_query(__field_reason) {
+                                    query.push(("reason", value));
+                                }
+                                nails::__rt::build_uri(path, &query)
+                            }
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "#[nails] must be given on each variant of an enum")]
+    fn test_derive_enum_level_attrs() {
+        derive_preroute2(quote! {
+            #[nails(path = "/api/posts/{id}")]
+            enum GetPostRequest {
+                Foo {}
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "#[nails(to_uri)] must be given on the enum")]
+    fn test_derive_enum_variant_to_uri() {
+        derive_preroute2(quote! {
+            enum GetPostRequest {
+                #[nails(path = "/api/posts", to_uri)]
+                Foo {}
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "no value expected in #[nails(to_uri)]")]
+    fn test_derive_to_uri_with_value() {
+        derive_preroute2(quote! {
+            #[nails(path = "/api/posts", to_uri = "yes")]
+            struct ListPostsRequest;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "#[nails(path)] is needed")]
+    fn test_derive_enum_missing_variant_path() {
+        derive_preroute2(quote! {
+            enum GetPostRequest {
+                #[nails(path = "/api/posts")]
+                List,
+                Get { id: u64 },
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Preroute cannot be derived for enums without variants")]
+    fn test_derive_empty_enum() {
+        derive_preroute2(quote! {
+            enum GetPostRequest {}
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Preroute cannot be derived for unions")]
+    fn test_derive_union() {
+        derive_preroute2(quote! {
+            #[nails(path = "/api/posts/{id}")]
+            union GetPostRequest {
+                id: u64,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_derive_tuple() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(path = "/api/posts/{id}", to_uri)]
+                struct GetPostRequest(
+                    #[nails(path = "id")]
+                    String,
+                    #[nails(query = "param1")]
+                    String,
+                );
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for GetPostRequest {
+                    fn path_prefix_hint() -> &'static str { "/api/posts/" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/api/posts/{id}",
+                        }]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
+                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                    && path_iter.next().map(|comp| {
+                                        <String as nails::__rt::FromPath>::matches_raw(comp, false)
+                                    }).unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        )
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
+                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                    && path_iter.next().map(|comp| {
+                                        <String as nails::__rt::FromPath>::matches_raw(comp, false)
+                                    }).unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let mut path_iter = path[1..].split("/");
+                        path_iter.next();
+                        path_iter.next();
+                        let pathcomp_id = <String as nails::__rt::FromPath>::from_raw_path(
+                            path_iter.next().expect("internal error: invalid path given"),
+                            false,
+                        ).expect("internal error: invalid path given");
+                        Ok(GetPostRequest(
+                            pathcomp_id,
+                            nails::__rt::FromQuery::from_query(
+                                if let Some(values) = query_hash.get("param1") {
+                                    values.as_slice()
+                                } else {
+                                    &[]
+                                }
+                            )?,
+                        ))
+                        })
+                    }
+                }
+                impl nails::__rt::ToUri for GetPostRequest {
+                    fn to_uri(&self) -> nails::__rt::Uri {
+                        let mut path = String::new();
+                        path.push_str("/api");
+                        path.push_str("/posts");
+                        path.push_str("/");
+                        path.push_str(&nails::__rt::ToPath::to_raw_path(&self.0));
+                        let mut query = Vec::new();
+                        for value in nails::__rt::ToQuery::to_query(&self.1) {
+                            query.push(("param1", value));
+                        }
+                        nails::__rt::build_uri(path, &query)
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_unit() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(path = "/ping")]
+                struct PingRequest;
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for PingRequest {
+                    fn path_prefix_hint() -> &'static str { "/ping" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/ping",
+                        }]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        )
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let mut path_iter = path[1..].split("/");
+                        path_iter.next();
+                        Ok(PingRequest)
+                        })
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_method_list() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(path = "/dav", method = ["PUT", "PATCH", "PROPFIND"])]
+                struct DavRequest;
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for DavRequest {
+                    fn path_prefix_hint() -> &'static str { "/dav" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![
+                                nails::__rt::Method::PUT,
+                                nails::__rt::Method::PATCH,
+                                nails::__rt::Method::from_bytes(b"PROPFIND").unwrap(),
+                            ],
+                            host: None,
+                            path: "/dav",
+                        }]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (
+                            *method == nails::__rt::Method::PUT
+                                || *method == nails::__rt::Method::PATCH
+                                || *method == nails::__rt::Method::from_bytes(b"PROPFIND").unwrap()
+                        ) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "dav").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        )
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "dav").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![
+                                nails::__rt::Method::PUT,
+                                nails::__rt::Method::PATCH,
+                                nails::__rt::Method::from_bytes(b"PROPFIND").unwrap(),
+                            ]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let mut path_iter = path[1..].split("/");
+                        path_iter.next();
+                        Ok(DavRequest)
+                        })
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_derive_host() {
+        assert_ts_eq!(
+            derive_preroute2(quote! {
+                #[nails(path = "/users", host = "{tenant}.example.com")]
+                struct ListUsersRequest {
+                    tenant: String,
+                }
+            })
+            .unwrap(),
+            quote! {
+                impl nails::__rt::Preroute for ListUsersRequest {
+                    fn path_prefix_hint() -> &'static str { "/users" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: Some("{tenant}.example.com"),
+                            path: "/users",
+                        }]
+                    }
+                    fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
+                        (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "users").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        )
+                    }
+                    fn match_head(head: &nails::__rt::RequestHead<'_>) -> bool {
+                        head.host().and_then(|host| nails::__rt::match_host(
+                            host,
+                            &[None, Some("example"), Some("com")]
+                        )).map(|labels| {
+                            <String as nails::__rt::FromPath>::matches(labels[0usize])
+                        }).unwrap_or(false)
+                    }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "users").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    fn from_request<'a>(
+                        req: nails::__rt::Request<nails::__rt::Body>
+                    ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
+                        nails::__rt::box_future(async move {
+                        let query_hash = nails::__rt::parse_query(req.uri().query().unwrap_or(""));
+                        let path = req.uri().path();
+                        let mut path_iter = path[1..].split("/");
+                        path_iter.next();
+                        let host_labels = nails::__rt::match_host(
+                            nails::__rt::request_host(&req).expect("internal error: invalid host given"),
+                            &[None, Some("example"), Some("com")],
+                        ).expect("internal error: invalid host given");
+                        let hostcomp_tenant = <String as nails::__rt::FromPath>::from_path(
+                            host_labels[0usize]
+                        ).expect("internal error: invalid host given");
+                        Ok(ListUsersRequest {
+                            tenant: hostcomp_tenant,
+                        })
+                        })
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`id` is bound in both the host and the path")]
+    fn test_derive_host_and_path_binding() {
+        derive_preroute2(quote! {
+            #[nails(path = "/users/{id}", host = "{id}.example.com")]
+            struct GetUserRequest {
+                id: String,
+            }
+        })
+        .unwrap();
+    }
 
-#[cfg(test)]
-#[cfg_attr(tarpaulin, skip)]
-mod tests {
-    use super::*;
+    #[test]
+    #[should_panic(expected = "This name doesn't exist in the endpoint host")]
+    fn test_derive_non_captured_host_name() {
+        derive_preroute2(quote! {
+            #[nails(path = "/users", host = "{tenant}.example.com")]
+            struct ListUsersRequest {
+                #[nails(host)]
+                tenant: String,
+                #[nails(host)]
+                region: String,
+            }
+        })
+        .unwrap();
+    }
 
     #[test]
-    fn test_derive1() {
+    fn test_derive_header() {
         assert_ts_eq!(
             derive_preroute2(quote! {
-                #[nails(path = "/api/posts/{id}")]
-                struct GetPostRequest {
-                    id: String,
-                    #[nails(query)]
-                    param1: String,
-                    #[nails(query = "param2rename")]
-                    param2: String,
-                    param3: String,
+                #[nails(path = "/ping")]
+                struct PingRequest {
+                    #[nails(header = "If-None-Match")]
+                    etag: Option<String>,
+                    #[nails(header)]
+                    x_request_id: String,
                 }
             })
             .unwrap(),
             quote! {
-                impl nails::__rt::Preroute for GetPostRequest {
-                    fn path_prefix_hint() -> &'static str { "/api/posts/" }
+                impl nails::__rt::Preroute for PingRequest {
+                    fn path_prefix_hint() -> &'static str { "/ping" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/ping",
+                        }]
+                    }
                     fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
                         (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
                             path.starts_with("/") && {
                                 let mut path_iter = path[1..].split("/");
-                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
-                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
-                                    && path_iter.next().map(|comp| {
-                                        <String as nails::__rt::FromPath>::matches(comp)
-                                    }).unwrap_or(false)
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
                                     && path_iter.next().is_none()
                             }
                         )
                     }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
                     fn from_request<'a>(
                         req: nails::__rt::Request<nails::__rt::Body>
                     ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
@@ -325,32 +1805,12 @@ mod tests {
                         let path = req.uri().path();
                         let mut path_iter = path[1..].split("/");
                         path_iter.next();
-                        path_iter.next();
-                        let pathcomp_id = <String as nails::__rt::FromPath>::from_path(
-                            path_iter.next().expect("internal error: invalid path given")
-                        ).expect("internal error: invalid path given");
-                        Ok(GetPostRequest {
-                            id: pathcomp_id,
-                            param1: nails::__rt::FromQuery::from_query(
-                                if let Some(values) = query_hash.get("param1") {
-                                    values.as_slice()
-                                } else {
-                                    &[]
-                                }
-                            )?,
-                            param2: nails::__rt::FromQuery::from_query(
-                                if let Some(values) = query_hash.get("param2rename") {
-                                    values.as_slice()
-                                } else {
-                                    &[]
-                                }
+                        Ok(PingRequest {
+                            etag: nails::__rt::FromHeader::from_header(
+                                &req.headers().get_all("if-none-match").iter().collect::<Vec<_>>()
                             )?,
-                            param3: nails::__rt::FromQuery::from_query(
-                                if let Some(values) = query_hash.get("param3") {
-                                    values.as_slice()
-                                } else {
-                                    &[]
-                                }
+                            x_request_id: nails::__rt::FromHeader::from_header(
+                                &req.headers().get_all("x-request-id").iter().collect::<Vec<_>>()
                             )?,
                         })
                         })
@@ -361,26 +1821,52 @@ mod tests {
     }
 
     #[test]
-    fn test_derive_post() {
+    fn test_derive_query_default() {
         assert_ts_eq!(
             derive_preroute2(quote! {
-                #[nails(path = "/api/posts", method = "POST")]
-                struct CreatePostRequest;
+                #[nails(path = "/ping", to_uri)]
+                struct PingRequest {
+                    #[nails(query, default)]
+                    offset: u32,
+                    #[nails(default = "20")]
+                    limit: u32,
+                    #[nails(query = "q", default = 'x')]
+                    c: char,
+                }
             })
             .unwrap(),
             quote! {
-                impl nails::__rt::Preroute for CreatePostRequest {
-                    fn path_prefix_hint() -> &'static str { "/api/posts" }
+                impl nails::__rt::Preroute for PingRequest {
+                    fn path_prefix_hint() -> &'static str { "/ping" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/ping",
+                        }]
+                    }
                     fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
-                        (*method == nails::__rt::Method::POST) && (
+                        (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
                             path.starts_with("/") && {
                                 let mut path_iter = path[1..].split("/");
-                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
-                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
                                     && path_iter.next().is_none()
                             }
                         )
                     }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
                     fn from_request<'a>(
                         req: nails::__rt::Request<nails::__rt::Body>
                     ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
@@ -389,9 +1875,41 @@ mod tests {
                         let path = req.uri().path();
                         let mut path_iter = path[1..].split("/");
                         path_iter.next();
-                        path_iter.next();
-                        Ok(CreatePostRequest)
+                        Ok(PingRequest {
+                            offset: if let Some(values) = query_hash.get("offset") {
+                                nails::__rt::FromQuery::from_query(values.as_slice())?
+                            } else {
+                                Default::default()
+                            },
+                            limit: if let Some(values) = query_hash.get("limit") {
+                                nails::__rt::FromQuery::from_query(values.as_slice())?
+                            } else {
+                                20
+                            },
+                            c: if let Some(values) = query_hash.get("q") {
+                                nails::__rt::FromQuery::from_query(values.as_slice())?
+                            } else {
+                                'x'
+                            },
                         })
+                        })
+                    }
+                }
+                impl nails::__rt::ToUri for PingRequest {
+                    fn to_uri(&self) -> nails::__rt::Uri {
+                        let mut path = String::new();
+                        path.push_str("/ping");
+                        let mut query = Vec::new();
+                        for value in nails::__rt::ToQuery::to_query(&self.offset) {
+                            query.push(("offset", value));
+                        }
+                        for value in nails::__rt::ToQuery::to_query(&self.limit) {
+                            query.push(("limit", value));
+                        }
+                        for value in nails::__rt::ToQuery::to_query(&self.c) {
+                            query.push(("q", value));
+                        }
+                        nails::__rt::build_uri(path, &query)
                     }
                 }
             },
@@ -399,46 +1917,76 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Preroute cannot be derived for enums or unions")]
-    fn test_derive_enum() {
+    #[should_panic(expected = "#[nails(default)] is only allowed for query fields")]
+    fn test_derive_default_for_path() {
         derive_preroute2(quote! {
             #[nails(path = "/api/posts/{id}")]
-            enum GetPostRequest {
-                Foo {}
+            struct GetPostRequest {
+                #[nails(default)]
+                id: u64,
             }
         })
         .unwrap();
     }
 
     #[test]
-    fn test_derive_tuple() {
+    #[should_panic(expected = "multiple #[nails(default)] definitions")]
+    fn test_derive_double_defaults() {
+        derive_preroute2(quote! {
+            #[nails(path = "/api/posts")]
+            struct ListPostsRequest {
+                #[nails(default, default = "20")]
+                limit: u32,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_derive_cookie() {
         assert_ts_eq!(
             derive_preroute2(quote! {
-                #[nails(path = "/api/posts/{id}")]
-                struct GetPostRequest(
-                    #[nails(path = "id")]
-                    String,
-                    #[nails(query = "param1")]
-                    String,
-                );
+                #[nails(path = "/ping")]
+                struct PingRequest {
+                    #[nails(cookie = "SID")]
+                    session_id: Option<String>,
+                    #[nails(cookie)]
+                    theme: String,
+                }
             })
             .unwrap(),
             quote! {
-                impl nails::__rt::Preroute for GetPostRequest {
-                    fn path_prefix_hint() -> &'static str { "/api/posts/" }
+                impl nails::__rt::Preroute for PingRequest {
+                    fn path_prefix_hint() -> &'static str { "/ping" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/ping",
+                        }]
+                    }
                     fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
                         (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
                             path.starts_with("/") && {
                                 let mut path_iter = path[1..].split("/");
-                                path_iter.next().map(|comp| comp == "api").unwrap_or(false)
-                                    && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
-                                    && path_iter.next().map(|comp| {
-                                        <String as nails::__rt::FromPath>::matches(comp)
-                                    }).unwrap_or(false)
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
                                     && path_iter.next().is_none()
                             }
                         )
                     }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
                     fn from_request<'a>(
                         req: nails::__rt::Request<nails::__rt::Body>
                     ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
@@ -447,20 +1995,23 @@ mod tests {
                         let path = req.uri().path();
                         let mut path_iter = path[1..].split("/");
                         path_iter.next();
-                        path_iter.next();
-                        let pathcomp_id = <String as nails::__rt::FromPath>::from_path(
-                            path_iter.next().expect("internal error: invalid path given")
-                        ).expect("internal error: invalid path given");
-                        Ok(GetPostRequest(
-                            pathcomp_id,
-                            nails::__rt::FromQuery::from_query(
-                                if let Some(values) = query_hash.get("param1") {
+                        let cookie_hash = nails::__rt::parse_cookies(req.headers());
+                        Ok(PingRequest {
+                            session_id: nails::__rt::FromCookie::from_cookie(
+                                if let Some(values) = cookie_hash.get("SID") {
                                     values.as_slice()
                                 } else {
                                     &[]
                                 }
                             )?,
-                        ))
+                            theme: nails::__rt::FromCookie::from_cookie(
+                                if let Some(values) = cookie_hash.get("theme") {
+                                    values.as_slice()
+                                } else {
+                                    &[]
+                                }
+                            )?,
+                        })
                         })
                     }
                 }
@@ -469,16 +2020,28 @@ mod tests {
     }
 
     #[test]
-    fn test_derive_unit() {
+    fn test_derive_query_struct() {
         assert_ts_eq!(
             derive_preroute2(quote! {
-                #[nails(path = "/ping")]
-                struct PingRequest;
+                #[nails(path = "/ping", to_uri)]
+                struct PingRequest {
+                    #[nails(query_struct)]
+                    filter: Query<Filter>,
+                    #[nails(query_struct = "p")]
+                    page: Option<Query<Page>>,
+                }
             })
             .unwrap(),
             quote! {
                 impl nails::__rt::Preroute for PingRequest {
                     fn path_prefix_hint() -> &'static str { "/ping" }
+                    fn route_patterns() -> Vec<nails::__rt::RoutePattern> {
+                        vec![nails::__rt::RoutePattern {
+                            methods: vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,],
+                            host: None,
+                            path: "/ping",
+                        }]
+                    }
                     fn match_path(method: &nails::__rt::Method, path: &str) -> bool {
                         (*method == nails::__rt::Method::GET || *method == nails::__rt::Method::HEAD) && (
                             path.starts_with("/") && {
@@ -488,6 +2051,19 @@ mod tests {
                             }
                         )
                     }
+                    fn allowed_methods(path: &str) -> Vec<nails::__rt::Method> {
+                        if (
+                            path.starts_with("/") && {
+                                let mut path_iter = path[1..].split("/");
+                                path_iter.next().map(|comp| comp == "ping").unwrap_or(false)
+                                    && path_iter.next().is_none()
+                            }
+                        ) {
+                            vec![nails::__rt::Method::GET, nails::__rt::Method::HEAD,]
+                        } else {
+                            Vec::new()
+                        }
+                    }
                     fn from_request<'a>(
                         req: nails::__rt::Request<nails::__rt::Body>
                     ) -> nails::__rt::BoxFuture<'a, Result<Self, nails::__rt::NailsError>> {
@@ -496,14 +2072,191 @@ mod tests {
                         let path = req.uri().path();
                         let mut path_iter = path[1..].split("/");
                         path_iter.next();
-                        Ok(PingRequest)
+                        let query_tree = nails::__rt::parse_nested_query(req.uri().query().unwrap_or(""));
+                        Ok(PingRequest {
+                            filter: nails::__rt::FromQueryStruct::from_query_struct(query_tree.get("filter"))?,
+                            page: nails::__rt::FromQueryStruct::from_query_struct(query_tree.get("p"))?,
                         })
+                        })
+                    }
+                }
+                impl nails::__rt::ToUri for PingRequest {
+                    fn to_uri(&self) -> nails::__rt::Uri {
+                        let mut path = String::new();
+                        path.push_str("/ping");
+                        let mut query = Vec::new();
+                        let query_pairs_0 = nails::__rt::ToQueryStruct::to_query_struct(&self.filter, "filter");
+                        query.extend(query_pairs_0.iter().map(|(key, value)| (key.as_str(), value.clone())));
+                        let query_pairs_1 = nails::__rt::ToQueryStruct::to_query_struct(&self.page, "p");
+                        query.extend(query_pairs_1.iter().map(|(key, value)| (key.as_str(), value.clone())));
+                        nails::__rt::build_uri(path, &query)
                     }
                 }
             },
         );
     }
 
+    #[test]
+    #[should_panic(expected = "Cannot have both #[nails(query)] and #[nails(query_struct)]")]
+    fn test_derive_query_and_query_struct() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(query, query_struct)]
+                filter: Query<Filter>,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Specify name with #[nails(query_struct = \\\"\\\")]")]
+    fn test_derive_missing_query_struct_name_for_position_field() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest(#[nails(query_struct)] Query<Filter>);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cookie name `session id`")]
+    fn test_derive_invalid_cookie_name() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(cookie = "session id")]
+                session_id: Option<String>,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot have both #[nails(header)] and #[nails(cookie)]")]
+    fn test_derive_header_and_cookie() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(header, cookie)]
+                session_id: Option<String>,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "no value expected in #[nails(session)]")]
+    fn test_derive_session_with_value() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(session = "sid")]
+                session: Session,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot have both #[nails(cookie)] and #[nails(session)]")]
+    fn test_derive_cookie_and_session() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(cookie, session)]
+                session: Session,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid header name `If None Match`")]
+    fn test_derive_invalid_header_name() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(header = "If None Match")]
+                etag: Option<String>,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot have both #[nails(query)] and #[nails(header)]")]
+    fn test_derive_header_and_query() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest {
+                #[nails(query, header)]
+                etag: Option<String>,
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Specify name with #[nails(header = \\\"\\\")]")]
+    fn test_derive_missing_header_name_for_position_field() {
+        derive_preroute2(quote! {
+            #[nails(path = "/ping")]
+            struct PingRequest(#[nails(header)] Option<String>);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate method `PUT`")]
+    fn test_derive_duplicate_methods() {
+        derive_preroute2(quote! {
+            #[nails(path = "/dav", method = ["PUT", "PUT"])]
+            struct DavRequest;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one method is needed in #[nails(method)]")]
+    fn test_derive_empty_methods() {
+        derive_preroute2(quote! {
+            #[nails(path = "/dav", method = [])]
+            struct DavRequest;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid method name `MK COL`")]
+    fn test_derive_invalid_method() {
+        derive_preroute2(quote! {
+            #[nails(path = "/dav", method = "MK COL")]
+            struct DavRequest;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "method names are case-sensitive; use `PATCH`")]
+    fn test_derive_lowercase_method() {
+        derive_preroute2(quote! {
+            #[nails(path = "/dav", method = ["PUT", "patch"])]
+            struct DavRequest;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "#[nails] must have an argument list")]
+    fn test_derive_struct_attr_without_list() {
+        derive_preroute2(quote! {
+            #[nails = "/dav"]
+            struct DavRequest;
+        })
+        .unwrap();
+    }
+
     #[test]
     #[should_panic(expected = "multiple #[nails(path)] definitions")]
     fn test_derive_double_paths() {
@@ -723,6 +2476,20 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    #[should_panic(expected = "#[nails(allow_encoded_slash)] is only allowed for path fields")]
+    fn test_derive_allow_encoded_slash_for_query() {
+        derive_preroute2(quote! {
+            #[nails(path = "/api/posts/{id}")]
+            struct GetPostRequest {
+                id: String,
+                #[nails(query, allow_encoded_slash)]
+                query: String,
+            }
+        })
+        .unwrap();
+    }
+
     #[test]
     #[should_panic(expected = "unknown option: `foo`")]
     fn test_derive_unknown_field_attr() {
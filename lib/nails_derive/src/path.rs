@@ -2,14 +2,17 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::str::FromStr;
 
+use nails_pattern::{Component, Constraint, Variable};
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
 
-// TODO: support recursive glob like `/admin/sidekiq/{path*}`
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct PathPattern {
     components: Vec<ComponentMatcher>,
     bindings: HashSet<String>,
+    constraints: HashMap<String, Constraint>,
+    /// Variables accepting `%2F`.
+    encoded_slash: HashSet<String>,
 }
 
 impl PathPattern {
@@ -21,10 +24,15 @@ impl PathPattern {
                     prefix.push_str("/");
                     prefix.push_str(s);
                 }
-                ComponentMatcher::Var(_) => {
+                ComponentMatcher::Var(_) | ComponentMatcher::Rest(_) => {
                     prefix.push_str("/");
                     return prefix;
                 }
+                ComponentMatcher::Partial { literals, .. } => {
+                    prefix.push('/');
+                    prefix.push_str(&literals[0]);
+                    return prefix;
+                }
             }
         }
         prefix
@@ -46,17 +54,56 @@ impl PathPattern {
                 }
                 ComponentMatcher::Var(var) => {
                     let field_ty = &fields[var].ty;
+                    let constraint = self.gen_constraint_check(var, quote! { comp });
+                    let allow_encoded_slash = self.encoded_slash.contains(var);
                     quote! {
                         path_iter.next().map(|comp| {
-                            <#field_ty as nails::__rt::FromPath>::matches(comp)
+                            #constraint
+                            <#field_ty as nails::__rt::FromPath>::matches_raw(comp, #allow_encoded_slash)
+                        }).unwrap_or(false) &&
+                    }
+                }
+                ComponentMatcher::Rest(var) => {
+                    let field_ty = &fields[var].ty;
+                    let constraint = self.gen_constraint_check(var, quote! { rest });
+                    let allow_encoded_slash = self.encoded_slash.contains(var);
+                    quote! {
+                        path_iter.next().map(|rest| {
+                            #constraint
+                            <#field_ty as nails::__rt::FromPathRest>::matches_raw_rest(
+                                rest,
+                                #allow_encoded_slash,
+                            )
+                        }).unwrap_or(false) &&
+                    }
+                }
+                ComponentMatcher::Partial { literals, vars } => {
+                    let conditions = vars.iter().enumerate().map(|(i, var)| {
+                        let field_ty = &fields[var].ty;
+                        let constraint = self.gen_constraint_check(var, quote! { values[#i] });
+                        let allow_encoded_slash = self.encoded_slash.contains(var);
+                        quote! {
+                            #constraint
+                            <#field_ty as nails::__rt::FromPath>::matches_raw(
+                                values[#i],
+                                #allow_encoded_slash
+                            )
+                        }
+                    });
+                    quote! {
+                        path_iter.next().and_then(|comp| {
+                            nails::__rt::split_path_component(comp, &[#(#literals),*])
+                        }).map(|values| {
+                            #(#conditions)&&*
                         }).unwrap_or(false) &&
                     }
                 }
             })
             .collect::<TokenStream>();
+        let path_iter = self.gen_path_iter(path.clone());
         quote! {(
             #path.starts_with("/") && {
-                let mut path_iter = #path[1..].split("/");
+                let mut path_iter = #path_iter;
                 #conditions
                 path_iter.next().is_none()
             }
@@ -83,21 +130,160 @@ impl PathPattern {
                     let var_ident = syn::Ident::new(&var_ident, Span::call_site());
                     vars.insert(var.clone(), var_ident.clone());
                     let field_ty = &fields[var].ty;
+                    let allow_encoded_slash = self.encoded_slash.contains(var);
                     quote! {
-                        let #var_ident = <#field_ty as nails::__rt::FromPath>::from_path(
-                            path_iter.next().expect("internal error: invalid path given")
+                        let #var_ident = <#field_ty as nails::__rt::FromPath>::from_raw_path(
+                            path_iter.next().expect("internal error: invalid path given"),
+                            #allow_encoded_slash,
                         ).expect("internal error: invalid path given");
                     }
                 }
+                ComponentMatcher::Rest(var) => {
+                    let var_ident = format!("pathcomp_{}", var);
+                    let var_ident = syn::Ident::new(&var_ident, Span::call_site());
+                    vars.insert(var.clone(), var_ident.clone());
+                    let field_ty = &fields[var].ty;
+                    let allow_encoded_slash = self.encoded_slash.contains(var);
+                    quote! {
+                        let #var_ident = <#field_ty as nails::__rt::FromPathRest>::from_raw_path_rest(
+                            path_iter.next().expect("internal error: invalid path given"),
+                            #allow_encoded_slash,
+                        ).expect("internal error: invalid path given");
+                    }
+                }
+                ComponentMatcher::Partial {
+                    literals,
+                    vars: comp_vars,
+                } => {
+                    let var_idents = comp_vars
+                        .iter()
+                        .map(|var| {
+                            let var_ident = format!("pathcomp_{}", var);
+                            let var_ident = syn::Ident::new(&var_ident, Span::call_site());
+                            vars.insert(var.clone(), var_ident.clone());
+                            var_ident
+                        })
+                        .collect::<Vec<_>>();
+                    let parsers = comp_vars.iter().enumerate().map(|(i, var)| {
+                        let field_ty = &fields[var].ty;
+                        let allow_encoded_slash = self.encoded_slash.contains(var);
+                        quote! {
+                            <#field_ty as nails::__rt::FromPath>::from_raw_path(
+                                values[#i],
+                                #allow_encoded_slash
+                            ).expect("internal error: invalid path given")
+                        }
+                    });
+                    quote! {
+                        let (#(#var_idents,)*) = {
+                            let values = nails::__rt::split_path_component(
+                                path_iter.next().expect("internal error: invalid path given"),
+                                &[#(#literals),*],
+                            ).expect("internal error: invalid path given");
+                            (#(#parsers,)*)
+                        };
+                    }
+                }
             })
             .collect::<TokenStream>();
+        let path_iter = self.gen_path_iter(path);
         let extractor = quote! {
-            let mut path_iter = #path[1..].split("/");
+            let mut path_iter = #path_iter;
             #extractors
         };
         (extractor, vars)
     }
 
+    /// Generates code to render the path into `path`, given references to the bound values.
+    pub(crate) fn gen_path_builder(
+        &self,
+        path: TokenStream,
+        values: &HashMap<String, TokenStream>,
+    ) -> TokenStream {
+        let pushes = self
+            .components
+            .iter()
+            .map(|comp| match comp {
+                ComponentMatcher::String(s) => {
+                    let s = format!("/{}", s);
+                    quote! {
+                        #path.push_str(#s);
+                    }
+                }
+                ComponentMatcher::Var(var) => {
+                    let value = &values[var];
+                    quote! {
+                        #path.push_str("/");
+                        #path.push_str(&nails::__rt::ToPath::to_raw_path(#value));
+                    }
+                }
+                ComponentMatcher::Rest(var) => {
+                    let value = &values[var];
+                    quote! {
+                        #path.push_str("/");
+                        #path.push_str(&nails::__rt::ToPathRest::to_raw_path_rest(#value));
+                    }
+                }
+                ComponentMatcher::Partial { literals, vars } => {
+                    let pushes = vars.iter().zip(&literals[1..]).map(|(var, literal)| {
+                        let value = &values[var];
+                        let literal = if literal.is_empty() {
+                            None
+                        } else {
+                            Some(quote! { #path.push_str(#literal); })
+                        };
+                        quote! {
+                            #path.push_str(&nails::__rt::ToPath::to_raw_path(#value));
+                            #literal
+                        }
+                    });
+                    let first = format!("/{}", literals[0]);
+                    quote! {
+                        #path.push_str(#first);
+                        #(#pushes)*
+                    }
+                }
+            })
+            .collect::<TokenStream>();
+        quote! {
+            let mut #path = String::new();
+            #pushes
+        }
+    }
+
+    // Generates `check &&` if the variable has a constraint.
+    fn gen_constraint_check(&self, var: &str, value: TokenStream) -> TokenStream {
+        if let Some(constraint) = self.constraints.get(var) {
+            let pattern = &constraint.pattern;
+            let negated = constraint.negated;
+            let allow_encoded_slash = self.encoded_slash.contains(var);
+            quote! {
+                ({
+                    static CONSTRAINT: nails::__rt::PathConstraint =
+                        nails::__rt::PathConstraint::new(#pattern, #negated);
+                    CONSTRAINT.matches_raw(#value, #allow_encoded_slash)
+                }) &&
+            }
+        } else {
+            quote! {}
+        }
+    }
+
+    // A catch-all variable takes everything after the preceding components.
+    fn gen_path_iter(&self, path: TokenStream) -> TokenStream {
+        if let Some(ComponentMatcher::Rest(_)) = self.components.last() {
+            let n = self.components.len();
+            quote! { #path[1..].splitn(#n, "/") }
+        } else {
+            quote! { #path[1..].split("/") }
+        }
+    }
+
+    /// Lets the variable accept `%2F` in its value.
+    pub(crate) fn allow_encoded_slash(&mut self, var: &str) {
+        self.encoded_slash.insert(var.to_owned());
+    }
+
     pub(crate) fn bindings(&self) -> &HashSet<String> {
         &self.bindings
     }
@@ -107,63 +293,35 @@ impl FromStr for PathPattern {
     type Err = ParseError;
 
     fn from_str(path: &str) -> Result<Self, Self::Err> {
-        if !path.starts_with("/") {
-            return Err(ParseError::new(path, "must start with slash"));
-        }
-        let components = path[1..]
-            .split("/")
-            .map(|c| -> Result<_, Self::Err> {
-                // TODO: support `{{`, `}}`
-                if c.contains("{") || c.contains("}") {
-                    if !c.starts_with("{") || !c.ends_with("}") {
-                        return Err(ParseError::new(
-                            path,
-                            "variable must span the whole path component",
-                        ));
-                    }
-                    let c = &c[1..c.len() - 1];
-                    if c.contains("{") || c.contains("}") {
-                        return Err(ParseError::new(
-                            path,
-                            "variable must span the whole path component",
-                        ));
-                    }
-                    if c == "" {
-                        return Err(ParseError::new(path, "variable must contain variable name"));
-                    }
-                    if !is_ident(c) {
-                        return Err(ParseError::new(
-                            path,
-                            "variable must be /[a-zA-Z_][a-zA-Z0-9_]*/",
-                        ));
-                    }
-                    Ok(ComponentMatcher::Var(c.to_owned()))
-                } else {
-                    // TODO: more sanity check
-                    Ok(ComponentMatcher::String(c.to_owned()))
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
+        let components =
+            nails_pattern::parse_path(path).map_err(|message| ParseError::new(path, &message))?;
         let mut bindings = HashSet::new();
-        for comp in &components {
-            match comp {
-                ComponentMatcher::String(_) => {}
-                ComponentMatcher::Var(name) => {
-                    let success = bindings.insert(name.clone());
-                    if !success {
-                        return Err(ParseError::new(
-                            path,
-                            &format!("duplicate name: `{}`", name),
-                        ));
-                    }
-                }
+        let mut constraints = HashMap::new();
+        let mut bind = |var: Variable| {
+            bindings.insert(var.name.clone());
+            if let Some(constraint) = var.constraint {
+                constraints.insert(var.name.clone(), constraint);
             }
-        }
+            var.name
+        };
+        let components = components
+            .into_iter()
+            .map(|comp| match comp {
+                Component::Literal(s) => ComponentMatcher::String(s),
+                Component::Var(var) => ComponentMatcher::Var(bind(var)),
+                Component::Rest(var) => ComponentMatcher::Rest(bind(var)),
+                Component::Partial { literals, vars } => ComponentMatcher::Partial {
+                    literals,
+                    vars: vars.into_iter().map(&mut bind).collect(),
+                },
+            })
+            .collect();
 
         Ok(Self {
             components,
             bindings,
+            constraints,
+            encoded_slash: HashSet::new(),
         })
     }
 }
@@ -172,6 +330,13 @@ impl FromStr for PathPattern {
 enum ComponentMatcher {
     String(String),
     Var(String),
+    Rest(String),
+    /// Variables mixed with literals, like `{name}.{ext}`.
+    /// `literals` surround the variables, so it is one longer than `vars`.
+    Partial {
+        literals: Vec<String>,
+        vars: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -205,14 +370,6 @@ impl std::error::Error for ParseError {
     }
 }
 
-fn is_ident(s: &str) -> bool {
-    let s = s.as_bytes();
-    s.len() > 0
-        && s != b"_"
-        && (s[0].is_ascii_alphabetic() || s[0] == b'_')
-        && s.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'_')
-}
-
 #[cfg(test)]
 #[cfg_attr(tarpaulin, skip)]
 mod tests {
@@ -238,21 +395,14 @@ mod tests {
         };
     }
 
-    #[test]
-    fn test_is_ident() {
-        assert!(is_ident("foo_bar2"));
-        assert!(is_ident("_foo_bar2"));
-        assert!(!is_ident("_"));
-        assert!(!is_ident("1st"));
-        assert!(!is_ident("foo-bar"));
-    }
-
     #[test]
     fn test_path_prefix() {
         let parse = <PathPattern as FromStr>::from_str;
         let parse = |s| parse(s).unwrap();
         assert_eq!(parse("/").path_prefix(), "/");
         assert_eq!(parse("/api/posts/{id}").path_prefix(), "/api/posts/");
+        assert_eq!(parse("/static/{*path}").path_prefix(), "/static/");
+        assert_eq!(parse("/api/v{version}/items").path_prefix(), "/api/v");
         assert_eq!(
             parse("/api/posts/{post_id}/comments/{id}").path_prefix(),
             "/api/posts/",
@@ -286,12 +436,53 @@ mod tests {
                     path_iter.next().map(|comp| comp == "api").unwrap_or(false)
                         && path_iter.next().map(|comp| comp == "posts").unwrap_or(false)
                         && path_iter.next().map(|comp| {
-                            <String as nails::__rt::FromPath>::matches(comp)
+                            <String as nails::__rt::FromPath>::matches_raw(comp, false)
                         }).unwrap_or(false)
                         && path_iter.next().is_none()
                 })
             },
         );
+
+        let field = syn::Field::parse_named
+            .parse2(quote! { slug: String })
+            .unwrap();
+        assert_ts_eq!(
+            parse("/{slug:!feed}")
+                .gen_path_condition(quote! { path }, &hash![(S("slug"), &field),]),
+            quote! {
+                (path.starts_with("/") && {
+                    let mut path_iter = path[1..].split("/");
+                    path_iter.next().map(|comp| {
+                        ({
+                            static CONSTRAINT: nails::__rt::PathConstraint =
+                                nails::__rt::PathConstraint::new("^(?:feed)$", true);
+                            CONSTRAINT.matches_raw(comp, false)
+                        }) && <String as nails::__rt::FromPath>::matches_raw(comp, false)
+                    }).unwrap_or(false)
+                        && path_iter.next().is_none()
+                })
+            },
+        );
+
+        let field = syn::Field::parse_named
+            .parse2(quote! { version: u32 })
+            .unwrap();
+        assert_ts_eq!(
+            parse("/v{version}/items")
+                .gen_path_condition(quote! { path }, &hash![(S("version"), &field),]),
+            quote! {
+                (path.starts_with("/") && {
+                    let mut path_iter = path[1..].split("/");
+                    path_iter.next().and_then(|comp| {
+                        nails::__rt::split_path_component(comp, &["v", ""])
+                    }).map(|values| {
+                        <u32 as nails::__rt::FromPath>::matches_raw(values[0usize], false)
+                    }).unwrap_or(false)
+                        && path_iter.next().map(|comp| comp == "items").unwrap_or(false)
+                        && path_iter.next().is_none()
+                })
+            },
+        );
     }
 
     #[test]
@@ -320,13 +511,64 @@ mod tests {
                 let mut path_iter = path[1..].split("/");
                 path_iter.next();
                 path_iter.next();
-                let pathcomp_id = <String as nails::__rt::FromPath>::from_path(
-                    path_iter.next().expect("internal error: invalid path given")
+                let pathcomp_id = <String as nails::__rt::FromPath>::from_raw_path(
+                    path_iter.next().expect("internal error: invalid path given"),
+                    false,
                 ).expect("internal error: invalid path given");
             },
         );
         assert_eq!(vars.len(), 1);
         assert_eq!(vars["id"], "pathcomp_id");
+
+        let field = syn::Field::parse_named
+            .parse2(quote! { path: Vec<String> })
+            .unwrap();
+        let (extractor, vars) = parse("/static/{*path}")
+            .gen_path_extractor(quote! { path }, &hash![(S("path"), &field),]);
+        assert_ts_eq!(
+            extractor,
+            quote! {
+                let mut path_iter = path[1..].splitn(2usize, "/");
+                path_iter.next();
+                let pathcomp_path = <Vec<String> as nails::__rt::FromPathRest>::from_raw_path_rest(
+                    path_iter.next().expect("internal error: invalid path given"),
+                    false,
+                ).expect("internal error: invalid path given");
+            },
+        );
+        assert_eq!(vars.len(), 1);
+        assert_eq!(vars["path"], "pathcomp_path");
+
+        let field1 = syn::Field::parse_named
+            .parse2(quote! { name: String })
+            .unwrap();
+        let field2 = syn::Field::parse_named
+            .parse2(quote! { ext: String })
+            .unwrap();
+        let (extractor, vars) = parse("/{name}.{ext}").gen_path_extractor(
+            quote! { path },
+            &hash![(S("name"), &field1), (S("ext"), &field2)],
+        );
+        assert_ts_eq!(
+            extractor,
+            quote! {
+                let mut path_iter = path[1..].split("/");
+                let (pathcomp_name, pathcomp_ext,) = {
+                    let values = nails::__rt::split_path_component(
+                        path_iter.next().expect("internal error: invalid path given"),
+                        &["", ".", ""],
+                    ).expect("internal error: invalid path given");
+                    (
+                        <String as nails::__rt::FromPath>::from_raw_path(values[0usize], false)
+                            .expect("internal error: invalid path given"),
+                        <String as nails::__rt::FromPath>::from_raw_path(values[1usize], false)
+                            .expect("internal error: invalid path given"),
+                    )
+                };
+            },
+        );
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars["ext"], "pathcomp_ext");
     }
 
     #[test]
@@ -338,6 +580,8 @@ mod tests {
             PathPattern {
                 components: vec![ComponentMatcher::String(S("")),],
                 bindings: hash_set![],
+                constraints: hash![],
+                encoded_slash: hash_set![],
             },
         );
         assert_eq!(
@@ -345,6 +589,8 @@ mod tests {
             PathPattern {
                 components: vec![ComponentMatcher::String(S("ping")),],
                 bindings: hash_set![],
+                constraints: hash![],
+                encoded_slash: hash_set![],
             },
         );
         assert_eq!(
@@ -355,6 +601,8 @@ mod tests {
                     ComponentMatcher::String(S("")),
                 ],
                 bindings: hash_set![],
+                constraints: hash![],
+                encoded_slash: hash_set![],
             },
         );
         assert_eq!(
@@ -366,6 +614,8 @@ mod tests {
                     ComponentMatcher::Var(S("id")),
                 ],
                 bindings: hash_set![S("id")],
+                constraints: hash![],
+                encoded_slash: hash_set![],
             },
         );
         assert_eq!(
@@ -379,6 +629,111 @@ mod tests {
                     ComponentMatcher::Var(S("id")),
                 ],
                 bindings: hash_set![S("post_id"), S("id")],
+                constraints: hash![],
+                encoded_slash: hash_set![],
+            },
+        );
+        assert_eq!(
+            parse("/api/posts/post_{id}"),
+            PathPattern {
+                components: vec![
+                    ComponentMatcher::String(S("api")),
+                    ComponentMatcher::String(S("posts")),
+                    ComponentMatcher::Partial {
+                        literals: vec![S("post_"), S("")],
+                        vars: vec![S("id")],
+                    },
+                ],
+                bindings: hash_set![S("id")],
+                constraints: hash![],
+                encoded_slash: hash_set![],
+            },
+        );
+        assert_eq!(
+            parse("/files/{name}.{ext}"),
+            PathPattern {
+                components: vec![
+                    ComponentMatcher::String(S("files")),
+                    ComponentMatcher::Partial {
+                        literals: vec![S(""), S("."), S("")],
+                        vars: vec![S("name"), S("ext")],
+                    },
+                ],
+                bindings: hash_set![S("name"), S("ext")],
+                constraints: hash![],
+                encoded_slash: hash_set![],
+            },
+        );
+        assert_eq!(
+            parse("/{{{id}}}/{{}}"),
+            PathPattern {
+                components: vec![
+                    ComponentMatcher::Partial {
+                        literals: vec![S("{"), S("}")],
+                        vars: vec![S("id")],
+                    },
+                    ComponentMatcher::String(S("{}")),
+                ],
+                bindings: hash_set![S("id")],
+                constraints: hash![],
+                encoded_slash: hash_set![],
+            },
+        );
+        assert_eq!(
+            parse("/static/{*path}"),
+            PathPattern {
+                components: vec![
+                    ComponentMatcher::String(S("static")),
+                    ComponentMatcher::Rest(S("path")),
+                ],
+                bindings: hash_set![S("path")],
+                constraints: hash![],
+                encoded_slash: hash_set![],
+            },
+        );
+    }
+
+    #[test]
+    fn test_parse_constraint() {
+        let parse = <PathPattern as FromStr>::from_str;
+        let parse = |s| parse(s).unwrap();
+        assert_eq!(
+            parse("/api/articles/{slug:!feed}/{id:\\d{1,8}}.{ext:\\{|[a-z]+}"),
+            PathPattern {
+                components: vec![
+                    ComponentMatcher::String(S("api")),
+                    ComponentMatcher::String(S("articles")),
+                    ComponentMatcher::Var(S("slug")),
+                    ComponentMatcher::Partial {
+                        literals: vec![S(""), S("."), S("")],
+                        vars: vec![S("id"), S("ext")],
+                    },
+                ],
+                bindings: hash_set![S("slug"), S("id"), S("ext")],
+                constraints: hash![
+                    (
+                        S("slug"),
+                        Constraint {
+                            pattern: S("^(?:feed)$"),
+                            negated: true,
+                        }
+                    ),
+                    (
+                        S("id"),
+                        Constraint {
+                            pattern: S("^(?:\\d{1,8})$"),
+                            negated: false,
+                        }
+                    ),
+                    (
+                        S("ext"),
+                        Constraint {
+                            pattern: S("^(?:\\{|[a-z]+)$"),
+                            negated: false,
+                        }
+                    )
+                ],
+                encoded_slash: hash_set![],
             },
         );
     }
@@ -390,25 +745,26 @@ mod tests {
         assert_eq!(parse_err(""), "must start with slash");
         assert_eq!(parse_err("api/posts/{id}"), "must start with slash",);
         assert_eq!(
-            parse_err("/api/posts/post_{id}"),
-            "variable must span the whole path component",
+            parse_err("/api/posts/}/"),
+            "unmatched `}`; use `}}` for a literal brace",
         );
         assert_eq!(
-            parse_err("/api/posts/{foo}_{bar}"),
-            "variable must span the whole path component",
+            parse_err("/api/posts/{barrr/"),
+            "unclosed `{`; use `{{` for a literal brace",
         );
         assert_eq!(
-            parse_err("/api/posts/}/"),
-            "variable must span the whole path component",
+            parse_err("/api/posts/}foo{/"),
+            "unmatched `}`; use `}}` for a literal brace",
         );
         assert_eq!(
-            parse_err("/api/posts/{barrr/"),
-            "variable must span the whole path component",
+            parse_err("/api/posts/{foo{bar}}/"),
+            "unclosed `{`; use `{{` for a literal brace",
         );
         assert_eq!(
-            parse_err("/api/posts/}foo{/"),
-            "variable must span the whole path component",
+            parse_err("/api/posts/{foo}{bar}"),
+            "adjacent variables are ambiguous; separate them with a literal",
         );
+        assert_eq!(parse_err("/api/posts/{id}.{id}"), "duplicate name: `id`",);
         assert_eq!(
             parse_err("/api/posts/{}/"),
             "variable must contain variable name",
@@ -421,6 +777,27 @@ mod tests {
             parse_err("/api/posts/{id}/comments/{id}"),
             "duplicate name: `id`",
         );
+        assert_eq!(
+            parse_err("/static/{*}"),
+            "variable must contain variable name"
+        );
+        assert_eq!(
+            parse_err("/static/{*path}/index.html"),
+            "catch-all variable must be the last path component",
+        );
+        assert_eq!(
+            parse_err("/api/posts/{id:}"),
+            "constraint must not be empty",
+        );
+        assert!(parse_err("/api/posts/{id:(}").starts_with("invalid constraint `(`: "));
+        assert_eq!(
+            parse_err("/api/posts/{id:\\d{1,8}"),
+            "unclosed `{`; use `{{` for a literal brace",
+        );
+        assert_eq!(
+            parse_err("/static/v{*path}"),
+            "catch-all variable must span the whole path component",
+        );
     }
 
     #[test]
@@ -1,6 +1,7 @@
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{token, Field, Fields, FieldsNamed, FieldsUnnamed, Ident};
+use syn::spanned::Spanned;
+use syn::{token, Field, Fields, FieldsNamed, FieldsUnnamed, Ident, Index, Member};
 
 pub(crate) trait FieldsExt {
     fn try_construct<F, T, E>(&self, ident: &Ident, func: F) -> Result<TokenStream, E>
@@ -64,3 +65,14 @@ impl FieldsExt for Fields {
         Ok(t)
     }
 }
+
+pub(crate) fn field_member(field: &Field, idx: usize) -> Member {
+    if let Some(ident) = &field.ident {
+        Member::Named(ident.clone())
+    } else {
+        Member::Unnamed(Index {
+            index: idx as u32,
+            span: field.span(),
+        })
+    }
+}
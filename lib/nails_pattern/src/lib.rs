@@ -0,0 +1,265 @@
+//! The syntax of path patterns like `/api/posts/{id}`.
+//!
+//! `nails_derive` generates the matchers from it and `nails` analyzes the route table with it,
+//! so both read a pattern the same way.
+
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Component {
+    Literal(String),
+    Var(Variable),
+    /// Catch-all variable like `{*rest}`, always the last component.
+    Rest(Variable),
+    /// Variables mixed with literals, like `{name}.{ext}`.
+    /// `literals` surround the variables, so it is one longer than `vars`.
+    Partial {
+        literals: Vec<String>,
+        vars: Vec<Variable>,
+    },
+}
+
+impl Component {
+    pub fn vars(&self) -> &[Variable] {
+        match self {
+            Component::Literal(_) => &[],
+            Component::Var(var) | Component::Rest(var) => std::slice::from_ref(var),
+            Component::Partial { vars, .. } => vars,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    pub constraint: Option<Constraint>,
+}
+
+/// A regex the whole value must match, or must not match if negated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    /// The anchored regex.
+    pub pattern: String,
+    pub negated: bool,
+}
+
+impl Constraint {
+    /// Parses the part after the colon, like `\d+` or `!feed`.
+    pub fn parse(constraint: &str) -> Result<Self, String> {
+        let (constraint, negated) = if let Some(constraint) = constraint.strip_prefix('!') {
+            (constraint, true)
+        } else {
+            (constraint, false)
+        };
+        if constraint.is_empty() {
+            return Err("constraint must not be empty".to_owned());
+        }
+        let pattern = format!("^(?:{})$", constraint);
+        if let Err(e) = regex::Regex::new(&pattern) {
+            return Err(format!("invalid constraint `{}`: {}", constraint, e));
+        }
+        Ok(Self { pattern, negated })
+    }
+}
+
+/// Splits a path pattern into its components, or returns what is wrong with it.
+pub fn parse_path(path: &str) -> Result<Vec<Component>, String> {
+    if !path.starts_with('/') {
+        return Err("must start with slash".to_owned());
+    }
+    let num_components = path[1..].split('/').count();
+    let components = path[1..]
+        .split('/')
+        .enumerate()
+        .map(|(i, c)| -> Result<_, String> {
+            let mut pieces = parse_component(c)?;
+            if pieces.len() <= 1 {
+                return match pieces.pop() {
+                    None => Ok(Component::Literal(String::new())),
+                    Some(Piece::Literal(s)) => Ok(Component::Literal(s)),
+                    Some(Piece::Var(var)) => Ok(Component::Var(var)),
+                    Some(Piece::Rest(var)) => {
+                        if i + 1 != num_components {
+                            return Err(
+                                "catch-all variable must be the last path component".to_owned()
+                            );
+                        }
+                        Ok(Component::Rest(var))
+                    }
+                };
+            }
+            let mut literals = vec![String::new()];
+            let mut vars = Vec::new();
+            for piece in pieces {
+                match piece {
+                    Piece::Literal(s) => *literals.last_mut().unwrap() = s,
+                    Piece::Var(var) => {
+                        vars.push(var);
+                        literals.push(String::new());
+                    }
+                    Piece::Rest(_) => {
+                        return Err(
+                            "catch-all variable must span the whole path component".to_owned()
+                        );
+                    }
+                }
+            }
+            Ok(Component::Partial { literals, vars })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut names = HashSet::new();
+    for var in components.iter().flat_map(Component::vars) {
+        if !names.insert(&var.name) {
+            return Err(format!("duplicate name: `{}`", var.name));
+        }
+    }
+    Ok(components)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Var(Variable),
+    Rest(Variable),
+}
+
+fn parse_component(component: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut rest = component;
+    while let Some(pos) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            literal.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err("unmatched `}`; use `}}` for a literal brace".to_owned());
+        }
+        let end = find_closing_brace(tail)
+            .filter(|&end| !tail[1..end].split(':').next().unwrap().contains('{'));
+        let end = end.ok_or_else(|| "unclosed `{`; use `{{` for a literal brace".to_owned())?;
+        let (name, constraint) = if let Some(colon) = tail[1..end].find(':') {
+            (&tail[1..colon + 1], Some(&tail[colon + 2..end]))
+        } else {
+            (&tail[1..end], None)
+        };
+        let (name, is_rest) = if let Some(name) = name.strip_prefix('*') {
+            (name, true)
+        } else {
+            (name, false)
+        };
+        if name.is_empty() {
+            return Err("variable must contain variable name".to_owned());
+        }
+        if !is_ident(name) {
+            return Err("variable must be /[a-zA-Z_][a-zA-Z0-9_]*/".to_owned());
+        }
+        let constraint = constraint.map(Constraint::parse).transpose()?;
+        if literal.is_empty() {
+            if let Some(Piece::Var(_)) | Some(Piece::Rest(_)) = pieces.last() {
+                return Err(
+                    "adjacent variables are ambiguous; separate them with a literal".to_owned(),
+                );
+            }
+        } else {
+            pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+        }
+        let var = Variable {
+            name: name.to_owned(),
+            constraint,
+        };
+        pieces.push(if is_rest {
+            Piece::Rest(var)
+        } else {
+            Piece::Var(var)
+        });
+        rest = &tail[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+// Braces may nest inside a constraint like `{id:\d{1,8}}`. Backslash escapes a brace.
+fn find_closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn is_ident(s: &str) -> bool {
+    let s = s.as_bytes();
+    !s.is_empty()
+        && s != b"_"
+        && (s[0].is_ascii_alphabetic() || s[0] == b'_')
+        && s.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'_')
+}
+
+#[cfg(test)]
+#[cfg_attr(tarpaulin, skip)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ident() {
+        assert!(is_ident("foo_bar2"));
+        assert!(is_ident("_foo_bar2"));
+        assert!(!is_ident("_"));
+        assert!(!is_ident("1st"));
+        assert!(!is_ident("foo-bar"));
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let var = |name: &str, constraint: Option<&str>| Variable {
+            name: name.to_owned(),
+            constraint: constraint.map(|c| Constraint::parse(c).unwrap()),
+        };
+        assert_eq!(
+            parse_path("/files/{name}.{ext:md|html}/{*rest}").unwrap(),
+            vec![
+                Component::Literal("files".to_owned()),
+                Component::Partial {
+                    literals: vec![String::new(), ".".to_owned(), String::new()],
+                    vars: vec![var("name", None), var("ext", Some("md|html"))],
+                },
+                Component::Rest(var("rest", None)),
+            ],
+        );
+        assert_eq!(
+            parse_path("/{{{id:\\d{1,8}}}}").unwrap(),
+            vec![Component::Partial {
+                literals: vec!["{".to_owned(), "}".to_owned()],
+                vars: vec![var("id", Some("\\d{1,8}"))],
+            }],
+        );
+        assert_eq!(
+            Constraint::parse("!feed").unwrap(),
+            Constraint {
+                pattern: "^(?:feed)$".to_owned(),
+                negated: true,
+            },
+        );
+        assert_eq!(parse_path("/{a}/{a}").unwrap_err(), "duplicate name: `a`",);
+    }
+}
@@ -17,7 +17,7 @@ pub struct AppCtxInner {
     pub secret_key: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Context)]
 pub struct AppCtx(pub Arc<AppCtxInner>);
 
 impl std::ops::Deref for AppCtx {
@@ -27,8 +27,6 @@ impl std::ops::Deref for AppCtx {
     }
 }
 
-impl Context for AppCtx {}
-
 impl AppCtx {
     pub fn new() -> Self {
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
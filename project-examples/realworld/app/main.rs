@@ -23,6 +23,8 @@ struct CommandOpt {
 enum SubcommandOpt {
     #[structopt(name = "server")]
     ServerCommandOpt(ServerCommandOpt),
+    #[structopt(name = "routes")]
+    RoutesCommandOpt(RoutesCommandOpt),
 }
 
 #[runtime::main]
@@ -30,11 +32,14 @@ async fn main() -> failure::Fallible<()> {
     dotenv::dotenv().ok();
 
     let opt = CommandOpt::from_args();
-    let ctx = AppCtx::new();
     match opt.subcommand {
         SubcommandOpt::ServerCommandOpt(ref server_opt) => {
+            let ctx = AppCtx::new();
             server(&ctx, server_opt).await?;
         }
+        SubcommandOpt::RoutesCommandOpt(ref routes_opt) => {
+            routes(routes_opt);
+        }
     }
     Ok(())
 }
@@ -46,7 +51,7 @@ pub(crate) struct ServerCommandOpt {
 }
 
 pub(crate) async fn server(ctx: &AppCtx, opt: &ServerCommandOpt) -> failure::Fallible<()> {
-    let svc = crate::routes::build_route(ctx);
+    let svc = crate::routes::build_route();
 
     let host: std::net::IpAddr = "127.0.0.1".parse().unwrap();
     let port = opt.port.unwrap_or(3000);
@@ -60,6 +65,63 @@ pub(crate) async fn server(ctx: &AppCtx, opt: &ServerCommandOpt) -> failure::Fal
     Ok(())
 }
 
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct RoutesCommandOpt {}
+
+pub(crate) fn routes(_opt: &RoutesCommandOpt) {
+    let svc = crate::routes::build_route();
+
+    let rows = svc
+        .routes()
+        .into_iter()
+        .map(|route| {
+            let methods = route
+                .methods
+                .iter()
+                .map(|method| method.as_str())
+                .collect::<Vec<_>>()
+                .join("|");
+            let handler = route.handler.unwrap_or("-");
+            let uri_pattern = format!("{}{}", route.host.unwrap_or_default(), route.path);
+            [
+                methods,
+                uri_pattern,
+                route.request_type.to_owned(),
+                handler.to_owned(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["Verb", "URI Pattern", "Request", "Handler"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    println!(
+        "{:>w0$} {:w1$} {:w2$} {}",
+        header[0],
+        header[1],
+        header[2],
+        header[3],
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+    );
+    for row in &rows {
+        println!(
+            "{:>w0$} {:w1$} {:w2$} {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        );
+    }
+}
+
 #[cfg(test)]
 #[derive(Debug)]
 pub(crate) struct TestServer {
@@ -88,7 +150,7 @@ async fn server_for_test(ctx: &AppCtx) -> failure::Fallible<TestServer> {
     use futures::channel::oneshot;
     use futures::prelude::*;
 
-    let svc = crate::routes::build_route(ctx);
+    let svc = crate::routes::build_route();
 
     let host: std::net::IpAddr = "127.0.0.1".parse().unwrap();
     let port = 0;
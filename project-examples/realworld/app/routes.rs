@@ -11,15 +11,13 @@ mod posts;
 mod tags;
 mod users;
 
-pub fn build_route(_ctx: &AppCtx) -> Service<AppCtx> {
+pub fn build_route() -> Service<AppCtx> {
     Service::builder()
         .add_function_route(index)
-        .add_function_route(users::create_user)
-        .add_function_route(users::login)
-        .add_function_route(posts::get_post)
-        .add_function_route(tags::list_tags)
-        .add_function_route(articles::list_articles)
-        .add_function_route(articles::list_feed_articles)
+        .mount("/api", users::routes())
+        .mount("/api", posts::routes())
+        .mount("/api", tags::routes())
+        .mount("/api", articles::routes())
         .finish()
 }
 
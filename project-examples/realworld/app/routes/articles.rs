@@ -1,18 +1,27 @@
 use hyper::{Body, Response};
 use nails::error::NailsError;
-use nails::Preroute;
+use nails::{Preroute, Router};
 use serde::Serialize;
 
 use crate::context::AppCtx;
 
+pub(crate) fn routes() -> Router<AppCtx> {
+    let mut router = Router::new();
+    router.add_function_route(list_articles);
+    router.add_function_route(list_feed_articles);
+    router
+}
+
 #[derive(Debug, Preroute)]
-#[nails(path = "/api/articles")]
+#[nails(path = "/articles")]
 pub(crate) struct ListArticlesRequest {
     tag: Option<String>,
     author: Option<String>,
     favorited: Option<String>,
-    limit: Option<i32>,
-    offset: Option<i32>,
+    #[nails(query, default = "20")]
+    limit: i32,
+    #[nails(query, default)]
+    offset: i32,
 }
 
 #[derive(Debug, Serialize)]
@@ -51,10 +60,12 @@ pub(crate) async fn list_articles(
 }
 
 #[derive(Debug, Preroute)]
-#[nails(path = "/api/articles/feed")]
+#[nails(path = "/articles/feed")]
 pub(crate) struct ListFeedArticlesRequest {
-    limit: Option<u32>,
-    offset: Option<u32>,
+    #[nails(query, default = "20")]
+    limit: u32,
+    #[nails(query, default)]
+    offset: u32,
 }
 
 #[derive(Debug, Serialize)]
@@ -1,12 +1,18 @@
 use hyper::{Body, Response};
 use nails::error::NailsError;
-use nails::Preroute;
+use nails::{Preroute, Router};
 use serde::Serialize;
 
 use crate::context::AppCtx;
 
+pub(crate) fn routes() -> Router<AppCtx> {
+    let mut router = Router::new();
+    router.add_function_route(get_post);
+    router
+}
+
 #[derive(Debug, Preroute)]
-#[nails(path = "/api/posts/{id}")]
+#[nails(path = "/posts/{id}")]
 pub(crate) struct GetPostRequest {
     id: u64,
 }
@@ -1,14 +1,20 @@
 use diesel::prelude::*;
 use hyper::{Body, Response};
 use nails::error::NailsError;
-use nails::Preroute;
+use nails::{Preroute, Router};
 use serde::Serialize;
 
 use crate::context::AppCtx;
 use crate::models::Tag;
 
+pub(crate) fn routes() -> Router<AppCtx> {
+    let mut router = Router::new();
+    router.add_function_route(list_tags);
+    router
+}
+
 #[derive(Debug, Preroute)]
-#[nails(path = "/api/tags")]
+#[nails(path = "/tags")]
 pub(crate) struct ListTagsRequest;
 
 #[derive(Debug, Serialize)]
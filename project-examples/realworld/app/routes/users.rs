@@ -4,15 +4,22 @@ use rand::prelude::*;
 use hyper::{Body, Response};
 use nails::error::NailsError;
 use nails::request::JsonBody;
-use nails::Preroute;
+use nails::{Preroute, Router};
 use serde::{Deserialize, Serialize};
 
 use crate::context::AppCtx;
 use crate::models;
 use crate::tokens::{self, Claims};
 
+pub(crate) fn routes() -> Router<AppCtx> {
+    let mut router = Router::new();
+    router.add_function_route(create_user);
+    router.add_function_route(login);
+    router
+}
+
 #[derive(Debug, Preroute)]
-#[nails(path = "/api/users", method = "POST")]
+#[nails(path = "/users", method = "POST")]
 pub(crate) struct CreateUserRequest {
     #[nails(body)]
     body: JsonBody<CreateUserRequestBody>,
@@ -64,7 +71,7 @@ pub(crate) async fn create_user(
 }
 
 #[derive(Debug, Preroute)]
-#[nails(path = "/api/users/login", method = "POST")]
+#[nails(path = "/users/login", method = "POST")]
 pub(crate) struct LoginRequest {
     #[nails(body)]
     body: JsonBody<LoginRequestBody>,